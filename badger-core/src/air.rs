@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+/// US AQI above which the top bar warns, unless configured otherwise
+pub const DEFAULT_AQI_WARNING: u16 = 100;
+
+/// Current air quality from the Open-Meteo air quality API. Everything is
+/// optional: pollen is only modelled for Europe, and any value can be null
+/// while the model catches up.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct AirQuality {
+    /// US EPA index, 0-500
+    pub us_aqi: Option<f32>,
+    /// Fine particulate matter, in μg/m³
+    pub pm2_5: Option<f32>,
+    // Pollen counts, in grains/m³
+    pub alder_pollen: Option<f32>,
+    pub birch_pollen: Option<f32>,
+    pub grass_pollen: Option<f32>,
+    pub ragweed_pollen: Option<f32>,
+}
+
+impl AirQuality {
+    /// Open-Meteo `current=` variables, one per field above
+    pub const FIELDS: &'static [&'static str] = &[
+        "us_aqi",
+        "pm2_5",
+        "alder_pollen",
+        "birch_pollen",
+        "grass_pollen",
+        "ragweed_pollen",
+    ];
+
+    /// The US AQI rounded to a whole number, if known
+    pub fn aqi(&self) -> Option<u16> {
+        self.us_aqi
+            .filter(|aqi| *aqi >= 0.0)
+            .map(|aqi| (aqi + 0.5) as u16)
+    }
+
+    /// The AQI when it is above `threshold`, i.e. worth a warning
+    pub fn warning(&self, threshold: u16) -> Option<u16> {
+        self.aqi().filter(|aqi| *aqi > threshold)
+    }
+}
+
+/// EPA category for a US AQI value, shortened to fit the page
+pub fn aqi_category(aqi: u16) -> &'static str {
+    match aqi {
+        0..=50 => "Good",
+        51..=100 => "Moderate",
+        101..=150 => "Unhealthy (sens.)",
+        151..=200 => "Unhealthy",
+        201..=300 => "Very unhealthy",
+        _ => "Hazardous",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_and_warns_above_threshold() {
+        let air = |aqi| AirQuality {
+            us_aqi: Some(aqi),
+            ..Default::default()
+        };
+
+        assert_eq!(air(42.4).aqi(), Some(42));
+        assert_eq!(air(100.4).warning(100), None);
+        assert_eq!(air(100.6).warning(100), Some(101));
+        assert_eq!(AirQuality::default().warning(0), None);
+        assert_eq!(air(-1.0).aqi(), None);
+    }
+
+    #[test]
+    fn categories_follow_epa_breakpoints() {
+        assert_eq!(aqi_category(0), "Good");
+        assert_eq!(aqi_category(50), "Good");
+        assert_eq!(aqi_category(51), "Moderate");
+        assert_eq!(aqi_category(101), "Unhealthy (sens.)");
+        assert_eq!(aqi_category(200), "Unhealthy");
+        assert_eq!(aqi_category(300), "Very unhealthy");
+        assert_eq!(aqi_category(301), "Hazardous");
+    }
+}
@@ -0,0 +1,453 @@
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+use serde::{Deserialize, Serialize};
+use time::{Date, Month};
+
+use crate::{
+    air::AirQuality,
+    http::{HttpError, Url},
+    units::Units,
+    weather::{
+        CurrentWeather, DailyForecast, FORECAST_DAYS, FORECAST_HOURS, Forecast, HourlyForecast,
+    },
+};
+
+pub const OPEN_METEO_FORECAST: &str = "http://api.open-meteo.com/v1/forecast";
+pub const OPEN_METEO_AIR_QUALITY: &str = "http://air-quality-api.open-meteo.com/v1/air-quality";
+pub const MET_NORWAY_FORECAST: &str = "https://api.met.no/weatherapi/locationforecast/2.0/compact";
+
+#[derive(Deserialize)]
+pub struct OpenMeteoResponse {
+    pub current: CurrentWeather,
+    /// Only present when `daily=` was requested
+    pub daily: Option<OpenMeteoDaily>,
+    /// Only present when `hourly=` was requested
+    pub hourly: Option<OpenMeteoHourly>,
+}
+
+#[derive(Deserialize)]
+pub struct OpenMeteoAirQualityResponse {
+    pub current: AirQuality,
+}
+
+/// Open-Meteo daily data, one array per variable
+#[derive(Deserialize)]
+pub struct OpenMeteoDaily {
+    time: Vec<String<10>, FORECAST_DAYS>,
+    temperature_2m_max: Vec<f32, FORECAST_DAYS>,
+    temperature_2m_min: Vec<f32, FORECAST_DAYS>,
+    weathercode: Vec<u8, FORECAST_DAYS>,
+    precipitation_probability_max: Vec<Option<u8>, FORECAST_DAYS>,
+}
+
+impl OpenMeteoDaily {
+    /// Zips the arrays into days, stopping at the shortest or at a date
+    /// that doesn't parse
+    pub fn forecast(&self) -> Forecast {
+        self.time
+            .iter()
+            .zip(&self.temperature_2m_max)
+            .zip(&self.temperature_2m_min)
+            .zip(&self.weathercode)
+            .zip(&self.precipitation_probability_max)
+            .map_while(|((((time, &high), &low), &weathercode), &precipitation)| {
+                Some(DailyForecast {
+                    weekday: parse_date(time)?.weekday().number_days_from_monday(),
+                    high,
+                    low,
+                    weathercode,
+                    precipitation_probability: precipitation,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Open-Meteo hourly data, one array per variable
+#[derive(Deserialize)]
+pub struct OpenMeteoHourly {
+    time: Vec<String<16>, FORECAST_HOURS>,
+    temperature_2m: Vec<f32, FORECAST_HOURS>,
+    precipitation_probability: Vec<Option<u8>, FORECAST_HOURS>,
+}
+
+impl OpenMeteoHourly {
+    /// `None` if the first time stamp doesn't parse
+    pub fn forecast(&self) -> Option<HourlyForecast> {
+        let start_hour = parse_hour(self.time.first()?)?;
+        let hours = self
+            .temperature_2m
+            .len()
+            .min(self.precipitation_probability.len());
+
+        Some(HourlyForecast {
+            start_hour,
+            temperature: self.temperature_2m.iter().take(hours).copied().collect(),
+            precipitation_probability: self
+                .precipitation_probability
+                .iter()
+                .take(hours)
+                .map(|p| p.unwrap_or(0))
+                .collect(),
+        })
+    }
+}
+
+/// Hour of `2026-01-02T03:00`
+fn parse_hour(time: &str) -> Option<u8> {
+    let (_, time) = time.split_once('T')?;
+    let hour = time.get(..2)?.parse::<u8>().ok()?;
+
+    (hour < 24).then_some(hour)
+}
+
+/// `2026-01-02`
+fn parse_date(date: &str) -> Option<Date> {
+    let mut parts = date.splitn(3, '-').map(|p| p.parse::<u16>().ok());
+
+    let year = parts.next()??;
+    let month = Month::try_from(u8::try_from(parts.next()??).ok()?).ok()?;
+    let day = u8::try_from(parts.next()??).ok()?;
+
+    Date::from_calendar_date(year.into(), month, day).ok()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub latitude: f32,
+    pub longitude: f32,
+}
+
+impl Location {
+    /// Parses decimal degrees, e.g. `20.661` and `-87.039`
+    pub fn parse(latitude: &str, longitude: &str) -> Option<Self> {
+        let latitude = latitude.trim().parse::<f32>().ok()?;
+        let longitude = longitude.trim().parse::<f32>().ok()?;
+
+        ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some(
+            Location {
+                latitude,
+                longitude,
+            },
+        )
+    }
+}
+
+/// Everything that goes into an Open-Meteo forecast request
+pub struct OpenMeteoQuery<'a> {
+    pub location: Location,
+    pub units: Units,
+    /// IANA name, or `auto` for the location's own zone
+    pub timezone: &'a str,
+    /// `current=` variables, normally [`CurrentWeather::FIELDS`]
+    pub current: &'a [&'a str],
+    /// `daily=` variables, normally [`DailyForecast::FIELDS`], or empty
+    pub daily: &'a [&'a str],
+    /// `hourly=` variables, normally [`HourlyForecast::FIELDS`], or empty
+    pub hourly: &'a [&'a str],
+}
+
+impl OpenMeteoQuery<'_> {
+    pub fn url(&self) -> Result<Url, HttpError> {
+        let mut url = Url::new();
+
+        write!(
+            url,
+            "{OPEN_METEO_FORECAST}?latitude={:.4}&longitude={:.4}&{}&timezone={}",
+            self.location.latitude,
+            self.location.longitude,
+            self.units.open_meteo_params(),
+            self.timezone,
+        )
+        .map_err(|_| HttpError::InvalidUrl)?;
+
+        write_list(&mut url, "current", self.current)?;
+        write_list(&mut url, "daily", self.daily)?;
+
+        write_list(&mut url, "hourly", self.hourly)?;
+
+        if !self.daily.is_empty() {
+            write!(url, "&forecast_days={FORECAST_DAYS}").map_err(|_| HttpError::InvalidUrl)?;
+        }
+
+        if !self.hourly.is_empty() {
+            write!(url, "&forecast_hours={FORECAST_HOURS}").map_err(|_| HttpError::InvalidUrl)?;
+        }
+
+        Ok(url)
+    }
+}
+
+/// Appends `&name=a,b,c`
+fn write_list(url: &mut Url, name: &str, fields: &[&str]) -> Result<(), HttpError> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    write!(url, "&{name}=").map_err(|_| HttpError::InvalidUrl)?;
+
+    for (i, field) in fields.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        write!(url, "{separator}{field}").map_err(|_| HttpError::InvalidUrl)?;
+    }
+
+    Ok(())
+}
+
+/// Open-Meteo air quality request for the `current` variables, normally
+/// [`AirQuality::FIELDS`]
+pub fn air_quality_url(location: Location, current: &[&str]) -> Result<Url, HttpError> {
+    let mut url = Url::new();
+
+    write!(
+        url,
+        "{OPEN_METEO_AIR_QUALITY}?latitude={:.4}&longitude={:.4}",
+        location.latitude, location.longitude
+    )
+    .map_err(|_| HttpError::InvalidUrl)?;
+
+    write_list(&mut url, "current", current)?;
+
+    Ok(url)
+}
+
+pub fn met_norway_url(location: Location) -> Result<Url, HttpError> {
+    let mut url = Url::new();
+
+    // MET asks for no more than 4 decimals, to help their caching
+    write!(
+        url,
+        "{MET_NORWAY_FORECAST}?lat={:.4}&lon={:.4}",
+        location.latitude, location.longitude
+    )
+    .map_err(|_| HttpError::InvalidUrl)?;
+
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCATION: Location = Location {
+        latitude: 20.661,
+        longitude: -87.039,
+    };
+
+    #[test]
+    fn parses_open_meteo_current() {
+        let body = br#"{"latitude":20.66,"current_units":{"temperature":"C"},"current":{"time":"2026-01-02T03:00","interval":900,"temperature":27.4,"weathercode":2,"relative_humidity_2m":74,"is_day":1}}"#;
+        let (response, _) = serde_json_core::from_slice::<OpenMeteoResponse>(body).unwrap();
+
+        assert_eq!(response.current.weathercode, 2);
+        assert_eq!(response.current.temperature, 27.4);
+        assert_eq!(response.current.relative_humidity_2m, 74.0);
+        assert!(response.current.is_daytime());
+    }
+
+    #[test]
+    fn builds_open_meteo_url() {
+        let query = OpenMeteoQuery {
+            location: LOCATION,
+            units: Units::Imperial,
+            timezone: "auto",
+            current: CurrentWeather::FIELDS,
+            daily: &[],
+            hourly: &[],
+        };
+
+        assert_eq!(
+            query.url().unwrap(),
+            "http://api.open-meteo.com/v1/forecast?latitude=20.6610&longitude=-87.0390\
+             &temperature_unit=fahrenheit&wind_speed_unit=mph&precipitation_unit=inch\
+             &timezone=auto&current=temperature,weathercode,relative_humidity_2m,is_day,\
+             apparent_temperature,wind_speed_10m,wind_direction_10m,precipitation,uv_index"
+        );
+
+        let query = OpenMeteoQuery {
+            daily: DailyForecast::FIELDS,
+            units: Units::Metric,
+            ..query
+        };
+
+        assert!(query.url().unwrap().ends_with(
+            "&daily=temperature_2m_max,temperature_2m_min,weathercode,\
+             precipitation_probability_max&forecast_days=4"
+        ));
+
+        let query = OpenMeteoQuery {
+            daily: &[],
+            hourly: HourlyForecast::FIELDS,
+            ..query
+        };
+
+        assert!(
+            query
+                .url()
+                .unwrap()
+                .ends_with("&hourly=temperature_2m,precipitation_probability&forecast_hours=24")
+        );
+    }
+
+    #[test]
+    fn parses_open_meteo_hourly() {
+        let body = br#"{"current":{"temperature":27.4,"weathercode":2,"relative_humidity_2m":74,"is_day":1},"hourly":{"time":["2026-01-02T22:00","2026-01-02T23:00","2026-01-03T00:00"],"temperature_2m":[25.5,24.9,24.1],"precipitation_probability":[5,null,40]}}"#;
+        let (response, _) = serde_json_core::from_slice::<OpenMeteoResponse>(body).unwrap();
+        let hourly = response.hourly.unwrap().forecast().unwrap();
+
+        assert_eq!(hourly.start_hour, 22);
+        assert_eq!(hourly.temperature, [25.5, 24.9, 24.1]);
+        assert_eq!(hourly.precipitation_probability, [5, 0, 40]);
+    }
+
+    #[test]
+    fn hourly_fields_match_struct() {
+        let mut body = std::string::String::from(
+            r#"{"current":{"temperature":1,"weathercode":1,"relative_humidity_2m":1,"is_day":1},"hourly":{"time":["2026-01-02T03:00"]"#,
+        );
+        for field in HourlyForecast::FIELDS {
+            body += &format!(r#","{field}":[1]"#);
+        }
+        body += "}}";
+
+        let (response, _) = serde_json_core::from_str::<OpenMeteoResponse>(&body).unwrap();
+        assert_eq!(
+            response
+                .hourly
+                .unwrap()
+                .forecast()
+                .unwrap()
+                .temperature
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn parses_open_meteo_daily() {
+        let body = br#"{"current":{"temperature":27.4,"weathercode":2,"relative_humidity_2m":74,"is_day":1},"daily_units":{"time":"iso8601"},"daily":{"time":["2026-01-02","2026-01-03","2026-01-04","2026-01-05"],"temperature_2m_max":[30.1,29.5,28.0,31.2],"temperature_2m_min":[24.0,23.8,22.9,24.4],"weathercode":[2,61,95,0],"precipitation_probability_max":[10,80,null,0]}}"#;
+        let (response, _) = serde_json_core::from_slice::<OpenMeteoResponse>(body).unwrap();
+        let forecast = response.daily.unwrap().forecast();
+
+        assert_eq!(forecast.len(), 4);
+        assert_eq!(
+            forecast[0],
+            DailyForecast {
+                weekday: 4,
+                high: 30.1,
+                low: 24.0,
+                weathercode: 2,
+                precipitation_probability: Some(10),
+            }
+        );
+        assert_eq!(forecast[2].precipitation_probability, None);
+        assert_eq!(forecast[3].weekday, 0);
+    }
+
+    #[test]
+    fn daily_fields_match_struct() {
+        let mut body = std::string::String::from(
+            r#"{"current":{"temperature":1,"weathercode":1,"relative_humidity_2m":1,"is_day":1},"daily":{"time":["2026-01-02"]"#,
+        );
+        for field in DailyForecast::FIELDS {
+            body += &format!(r#","{field}":[1]"#);
+        }
+        body += "}}";
+
+        let (response, _) = serde_json_core::from_str::<OpenMeteoResponse>(&body).unwrap();
+        assert_eq!(response.daily.unwrap().forecast().len(), 1);
+    }
+
+    #[test]
+    fn requested_fields_match_struct() {
+        let object = |bad: Option<usize>| {
+            let mut body = std::string::String::from(r#"{"current":{"time":"2026-01-02T03:00""#);
+            for (i, field) in CurrentWeather::FIELDS.iter().enumerate() {
+                let value = if Some(i) == bad { r#""x""# } else { "1" };
+                body += &format!(r#","{field}":{value}"#);
+            }
+            body + "}}"
+        };
+
+        // Every requested field is enough to decode the struct...
+        let body = object(None);
+        let (response, _) = serde_json_core::from_str::<OpenMeteoResponse>(&body).unwrap();
+        assert_eq!(response.current.uv_index, Some(1.0));
+
+        // ...and every one of them is read, so a value of the wrong type
+        // fails even for the optional ones
+        for i in 0..CurrentWeather::FIELDS.len() {
+            let body = object(Some(i));
+            assert!(
+                serde_json_core::from_str::<OpenMeteoResponse>(&body).is_err(),
+                "{} is requested but not decoded",
+                CurrentWeather::FIELDS[i]
+            );
+        }
+    }
+
+    #[test]
+    fn extended_fields_are_optional() {
+        let body = br#"{"current":{"temperature":27.4,"weathercode":2,"relative_humidity_2m":74,"is_day":1,"wind_speed_10m":12.5,"uv_index":null}}"#;
+        let (response, _) = serde_json_core::from_slice::<OpenMeteoResponse>(body).unwrap();
+
+        assert_eq!(response.current.wind_speed_10m, Some(12.5));
+        assert_eq!(response.current.uv_index, None);
+        assert_eq!(response.current.apparent_temperature, None);
+    }
+
+    #[test]
+    fn builds_air_quality_url() {
+        assert_eq!(
+            air_quality_url(LOCATION, AirQuality::FIELDS).unwrap(),
+            "http://air-quality-api.open-meteo.com/v1/air-quality?latitude=20.6610&longitude=-87.0390\
+             &current=us_aqi,pm2_5,alder_pollen,birch_pollen,grass_pollen,ragweed_pollen"
+        );
+    }
+
+    #[test]
+    fn parses_air_quality() {
+        let body = br#"{"latitude":52.52,"current_units":{"us_aqi":"USAQI"},"current":{"time":"2026-01-02T03:00","interval":3600,"us_aqi":57,"pm2_5":14.3,"alder_pollen":null,"birch_pollen":12.5,"grass_pollen":0.0,"ragweed_pollen":null}}"#;
+        let (response, _) =
+            serde_json_core::from_slice::<OpenMeteoAirQualityResponse>(body).unwrap();
+
+        assert_eq!(response.current.aqi(), Some(57));
+        assert_eq!(response.current.pm2_5, Some(14.3));
+        assert_eq!(response.current.alder_pollen, None);
+        assert_eq!(response.current.birch_pollen, Some(12.5));
+    }
+
+    #[test]
+    fn air_quality_fields_match_struct() {
+        for bad in 0..AirQuality::FIELDS.len() {
+            let mut body = std::string::String::from(r#"{"current":{"time":"2026-01-02T03:00""#);
+            for (i, field) in AirQuality::FIELDS.iter().enumerate() {
+                let value = if i == bad { r#""x""# } else { "1" };
+                body += &format!(r#","{field}":{value}"#);
+            }
+            body += "}}";
+
+            assert!(
+                serde_json_core::from_str::<OpenMeteoAirQualityResponse>(&body).is_err(),
+                "{} is requested but not decoded",
+                AirQuality::FIELDS[bad]
+            );
+        }
+    }
+
+    #[test]
+    fn builds_met_norway_url() {
+        assert_eq!(
+            met_norway_url(LOCATION).unwrap(),
+            "https://api.met.no/weatherapi/locationforecast/2.0/compact?lat=20.6610&lon=-87.0390"
+        );
+    }
+
+    #[test]
+    fn validates_location() {
+        assert_eq!(Location::parse(" 20.661", "-87.039 "), Some(LOCATION));
+        assert_eq!(Location::parse("91", "0"), None);
+        assert_eq!(Location::parse("0", "east"), None);
+    }
+}
@@ -0,0 +1,51 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BatteryState {
+    Error,
+    UsbPower,
+    Battery(u8),
+}
+
+pub fn get_battery_state(voltage: f32) -> BatteryState {
+    match voltage {
+        x if x > 4.5 => BatteryState::UsbPower,
+        x => {
+            let max_v = 4.2;
+            let min_v = 3.1;
+
+            let actual = x.clamp(min_v, max_v);
+            let percentage = (actual - min_v) / (max_v - min_v);
+
+            BatteryState::Battery((percentage * 100.0) as u8)
+        }
+    }
+}
+
+/// Converts an averaged 12-bit ADC reading of VSYS (through the 1/3
+/// divider) to volts
+pub fn adc_to_voltage(raw: u16) -> f32 {
+    ((raw as f32) / 4095.0) * 3.3 * 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usb_power_above_battery_range() {
+        assert_eq!(get_battery_state(5.0), BatteryState::UsbPower);
+    }
+
+    #[test]
+    fn battery_percentage_is_clamped() {
+        assert_eq!(get_battery_state(4.4), BatteryState::Battery(100));
+        assert_eq!(get_battery_state(2.5), BatteryState::Battery(0));
+        assert_eq!(get_battery_state(3.65), BatteryState::Battery(50));
+    }
+
+    #[test]
+    fn adc_full_scale() {
+        assert!((adc_to_voltage(4095) - 9.9).abs() < 0.001);
+        assert_eq!(adc_to_voltage(0), 0.0);
+    }
+}
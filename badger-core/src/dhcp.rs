@@ -0,0 +1,178 @@
+//! Just enough of a DHCP server (RFC 2131) for the setup access point, so a
+//! phone that joins it gets an address. There's no lease table: every
+//! client is offered an address picked from its MAC, which is plenty for the
+//! one or two clients setup ever sees. The badge is also the router and the
+//! DNS server, see [`crate::dns`].
+
+pub const SERVER_PORT: u16 = 67;
+pub const CLIENT_PORT: u16 = 68;
+
+/// BOOTP header plus the magic cookie, where the options start
+const HEADER_SIZE: usize = 240;
+
+/// Replies are padded to the BOOTP minimum, which covers our options
+pub const REPLY_SIZE: usize = 300;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const BOOT_REQUEST: u8 = 1;
+const BOOT_REPLY: u8 = 2;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DNS_SERVER: u8 = 6;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_ID: u8 = 54;
+const OPTION_END: u8 = 255;
+
+const DISCOVER: u8 = 1;
+const OFFER: u8 = 2;
+const REQUEST: u8 = 3;
+const ACK: u8 = 5;
+
+/// Long enough to outlast setup
+const LEASE_SECONDS: u32 = 3600;
+
+/// Answers a DISCOVER with an OFFER and a REQUEST with an ACK, writing the
+/// reply to `reply` and returning its length. Anything else gets no answer.
+/// `server` is our address, clients get one in the same /24. The reply goes
+/// to the broadcast address, as the client has no address yet.
+pub fn reply(request: &[u8], server: [u8; 4], reply: &mut [u8; REPLY_SIZE]) -> Option<usize> {
+    if request.len() < HEADER_SIZE
+        || request[0] != BOOT_REQUEST
+        || request[236..HEADER_SIZE] != MAGIC_COOKIE
+    {
+        return None;
+    }
+
+    let message_type = match message_type(&request[HEADER_SIZE..])? {
+        DISCOVER => OFFER,
+        REQUEST => ACK,
+        _ => return None,
+    };
+
+    let client = client_address(server, &request[28..34]);
+
+    reply.fill(0);
+    reply[0] = BOOT_REPLY;
+    // Hardware type and address length, transaction ID and flags
+    reply[1..3].copy_from_slice(&request[1..3]);
+    reply[4..8].copy_from_slice(&request[4..8]);
+    reply[10..12].copy_from_slice(&request[10..12]);
+    reply[16..20].copy_from_slice(&client);
+    reply[20..24].copy_from_slice(&server);
+    // Relay agent and client hardware address
+    reply[24..44].copy_from_slice(&request[24..44]);
+    reply[236..HEADER_SIZE].copy_from_slice(&MAGIC_COOKIE);
+
+    let lease = LEASE_SECONDS.to_be_bytes();
+    let [a, b, c, d] = server;
+    let options: [&[u8]; 6] = [
+        &[OPTION_MESSAGE_TYPE, 1, message_type],
+        &[OPTION_SERVER_ID, 4, a, b, c, d],
+        &[OPTION_LEASE_TIME, 4, lease[0], lease[1], lease[2], lease[3]],
+        &[OPTION_SUBNET_MASK, 4, 255, 255, 255, 0],
+        &[OPTION_ROUTER, 4, a, b, c, d],
+        &[OPTION_DNS_SERVER, 4, a, b, c, d],
+    ];
+
+    let mut at = HEADER_SIZE;
+    for option in options {
+        reply[at..at + option.len()].copy_from_slice(option);
+        at += option.len();
+    }
+    reply[at] = OPTION_END;
+
+    Some(REPLY_SIZE)
+}
+
+/// The DHCP message type option, if there is one
+fn message_type(mut options: &[u8]) -> Option<u8> {
+    loop {
+        match options {
+            [OPTION_END, ..] | [] => return None,
+            [OPTION_PAD, rest @ ..] => options = rest,
+            [OPTION_MESSAGE_TYPE, 1, kind, ..] => return Some(*kind),
+            [_, len, rest @ ..] => options = rest.get(*len as usize..)?,
+            [_] => return None,
+        }
+    }
+}
+
+/// Host 2 to 251 of the server's /24, from the last byte of the MAC
+fn client_address(server: [u8; 4], mac: &[u8]) -> [u8; 4] {
+    let host = 2 + mac[5] % 250;
+
+    [server[0], server[1], server[2], host]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: [u8; 4] = [192, 168, 4, 1];
+    const MAC: [u8; 6] = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    fn request(options: &[u8]) -> std::vec::Vec<u8> {
+        let mut packet = std::vec![0u8; HEADER_SIZE];
+        packet[0] = BOOT_REQUEST;
+        packet[1] = 1;
+        packet[2] = 6;
+        packet[4..8].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        packet[10] = 0x80;
+        packet[28..34].copy_from_slice(&MAC);
+        packet[236..240].copy_from_slice(&MAGIC_COOKIE);
+        packet.extend_from_slice(options);
+        packet
+    }
+
+    fn answer(options: &[u8]) -> Option<[u8; REPLY_SIZE]> {
+        let mut buf = [0xaa; REPLY_SIZE];
+        reply(&request(options), SERVER, &mut buf).map(|_| buf)
+    }
+
+    #[test]
+    fn offers_then_acks_an_address() {
+        // Padding and a hostname option before the message type
+        let offer = answer(&[0, 12, 3, b'p', b'h', b'o', 53, 1, DISCOVER, 255]).unwrap();
+
+        assert_eq!(offer[0], BOOT_REPLY);
+        assert_eq!(offer[4..8], [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(offer[10], 0x80);
+        assert_eq!(offer[16..20], [192, 168, 4, 2 + 0x55]);
+        assert_eq!(offer[28..34], MAC);
+        assert_eq!(offer[240..243], [OPTION_MESSAGE_TYPE, 1, OFFER]);
+        assert_eq!(offer[243..249], [OPTION_SERVER_ID, 4, 192, 168, 4, 1]);
+        assert_eq!(offer[261..267], [OPTION_ROUTER, 4, 192, 168, 4, 1]);
+        assert_eq!(offer[267..273], [OPTION_DNS_SERVER, 4, 192, 168, 4, 1]);
+        assert_eq!(offer[273], OPTION_END);
+
+        let ack = answer(&[53, 1, REQUEST, 50, 4, 192, 168, 4, 87, 255]).unwrap();
+
+        assert_eq!(ack[16..20], offer[16..20]);
+        assert_eq!(ack[240..243], [OPTION_MESSAGE_TYPE, 1, ACK]);
+    }
+
+    #[test]
+    fn ignores_everything_else() {
+        // Release, no message type, a truncated option
+        assert_eq!(answer(&[53, 1, 7, 255]), None);
+        assert_eq!(answer(&[255]), None);
+        assert_eq!(answer(&[12, 40, b'p']), None);
+
+        let mut reply_to_reply = request(&[53, 1, DISCOVER, 255]);
+        reply_to_reply[0] = BOOT_REPLY;
+        assert_eq!(reply(&reply_to_reply, SERVER, &mut [0; REPLY_SIZE]), None);
+        assert_eq!(reply(&[1; 100], SERVER, &mut [0; REPLY_SIZE]), None);
+    }
+
+    #[test]
+    fn never_hands_out_the_server_or_broadcast() {
+        for last in 0..=255 {
+            let host = client_address(SERVER, &[0, 0, 0, 0, 0, last])[3];
+            assert!((2..=251).contains(&host));
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Just enough of a DNS server (RFC 1035) for the setup access point: every
+//! name resolves to the badge, so a phone that checks for a captive portal,
+//! or a browser given any name, ends up at the setup form.
+
+pub const SERVER_PORT: u16 = 53;
+
+/// Largest reply, which is the classic UDP limit
+pub const REPLY_SIZE: usize = 512;
+
+const HEADER_SIZE: usize = 12;
+
+/// Question name pointer, type, class, TTL, length and address
+const ANSWER_SIZE: usize = 16;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_OPCODE: u16 = 0x7800;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+/// Short, so nothing remembers the badge once setup is over
+const TTL_SECONDS: u32 = 10;
+
+/// Answers a standard query with one question, writing the reply to `reply`
+/// and returning its length. An A query gets `address`, any other type an
+/// empty answer, so clients fall back to the A record. Anything else gets
+/// no reply.
+pub fn reply(query: &[u8], address: [u8; 4], reply: &mut [u8; REPLY_SIZE]) -> Option<usize> {
+    let header = query.get(..HEADER_SIZE)?;
+    let flags = u16::from_be_bytes([header[2], header[3]]);
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+
+    if flags & (FLAG_RESPONSE | FLAG_OPCODE) != 0 || questions != 1 {
+        return None;
+    }
+
+    let question_len = question_len(&query[HEADER_SIZE..])?;
+    let question = &query[HEADER_SIZE..HEADER_SIZE + question_len];
+    let kind = u16::from_be_bytes([question[question_len - 4], question[question_len - 3]]);
+    let class = u16::from_be_bytes([question[question_len - 2], question[question_len - 1]]);
+    let answers = u16::from(kind == TYPE_A && class == CLASS_IN);
+
+    let len = HEADER_SIZE + question_len + usize::from(answers) * ANSWER_SIZE;
+    if len > REPLY_SIZE {
+        return None;
+    }
+
+    let flags = FLAG_RESPONSE | FLAG_AUTHORITATIVE | (flags & FLAG_RECURSION_DESIRED);
+
+    reply[..2].copy_from_slice(&header[..2]);
+    reply[2..4].copy_from_slice(&flags.to_be_bytes());
+    reply[4..6].copy_from_slice(&1u16.to_be_bytes());
+    reply[6..8].copy_from_slice(&answers.to_be_bytes());
+    reply[8..HEADER_SIZE].fill(0);
+    reply[HEADER_SIZE..HEADER_SIZE + question_len].copy_from_slice(question);
+
+    if answers == 1 {
+        let answer = &mut reply[HEADER_SIZE + question_len..len];
+        // Points back at the name in the question
+        answer[..2].copy_from_slice(&[0xc0, HEADER_SIZE as u8]);
+        answer[2..4].copy_from_slice(&TYPE_A.to_be_bytes());
+        answer[4..6].copy_from_slice(&CLASS_IN.to_be_bytes());
+        answer[6..10].copy_from_slice(&TTL_SECONDS.to_be_bytes());
+        answer[10..12].copy_from_slice(&4u16.to_be_bytes());
+        answer[12..].copy_from_slice(&address);
+    }
+
+    Some(len)
+}
+
+/// Length of the question at the start of `buf`: the name as labels, then
+/// its type and class
+fn question_len(buf: &[u8]) -> Option<usize> {
+    let mut at = 0;
+
+    loop {
+        match *buf.get(at)? {
+            0 => break,
+            // Compression pointers have no place in a query's only name
+            len if len & 0xc0 != 0 => return None,
+            len => at += 1 + len as usize,
+        }
+    }
+
+    let len = at + 1 + 4;
+
+    (len <= buf.len()).then_some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: [u8; 4] = [192, 168, 4, 1];
+
+    fn query(name: &[&str], kind: u16, flags: u16) -> std::vec::Vec<u8> {
+        let mut packet = std::vec![0x12, 0x34];
+        packet.extend_from_slice(&flags.to_be_bytes());
+        packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&kind.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    fn answer(query: &[u8]) -> Option<std::vec::Vec<u8>> {
+        let mut buf = [0xaa; REPLY_SIZE];
+        reply(query, ADDRESS, &mut buf).map(|len| buf[..len].to_vec())
+    }
+
+    #[test]
+    fn answers_every_name_with_the_badge() {
+        let query = query(&["connectivitycheck", "gstatic", "com"], TYPE_A, 0x0100);
+        let reply = answer(&query).unwrap();
+
+        assert_eq!(reply.len(), query.len() + ANSWER_SIZE);
+        assert_eq!(reply[..2], [0x12, 0x34]);
+        assert_eq!(reply[2..4], [0x85, 0x00]);
+        assert_eq!(reply[4..12], [0, 1, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(reply[12..query.len()], query[12..]);
+        assert_eq!(
+            reply[query.len()..],
+            [0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 10, 0, 4, 192, 168, 4, 1]
+        );
+    }
+
+    #[test]
+    fn other_types_get_no_records() {
+        // AAAA
+        let query = query(&["badger", "local"], 28, 0);
+        let reply = answer(&query).unwrap();
+
+        assert_eq!(reply.len(), query.len());
+        assert_eq!(reply[2..8], [0x84, 0x00, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn ignores_everything_else() {
+        // A response, an inverse query, a truncated name and a pointer
+        assert_eq!(answer(&query(&["a"], TYPE_A, 0x8000)), None);
+        assert_eq!(answer(&query(&["a"], TYPE_A, 0x0800)), None);
+        assert_eq!(answer(&query(&["example"], TYPE_A, 0)[..18]), None);
+        assert_eq!(
+            answer(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12]),
+            None
+        );
+
+        let mut two_questions = query(&["a"], TYPE_A, 0);
+        two_questions[5] = 2;
+        assert_eq!(answer(&two_questions), None);
+        assert_eq!(answer(&[0; 11]), None);
+    }
+}
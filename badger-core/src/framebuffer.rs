@@ -0,0 +1,92 @@
+use core::convert::Infallible;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+use crate::layout::{HEIGHT, WIDTH};
+
+const ROW_BYTES: usize = (WIDTH as usize).div_ceil(8);
+const BUFFER_SIZE: usize = ROW_BYTES * HEIGHT as usize;
+
+/// In-memory 1-bit draw target with the same geometry as the UC8151 panel.
+///
+/// Pixels are packed row-major, MSB first, with a set bit meaning black
+/// (`BinaryColor::Off`), which is the layout of a binary PBM raster.
+pub struct Framebuffer {
+    buffer: [u8; BUFFER_SIZE],
+}
+
+impl Framebuffer {
+    /// A blank (all white) frame, like a freshly cleared panel
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; BUFFER_SIZE],
+        }
+    }
+
+    pub fn get_pixel(&self, point: Point) -> Option<BinaryColor> {
+        let (index, mask) = Self::locate(point)?;
+
+        match self.buffer[index] & mask {
+            0 => Some(BinaryColor::On),
+            _ => Some(BinaryColor::Off),
+        }
+    }
+
+    pub fn set_pixel(&mut self, point: Point, color: BinaryColor) {
+        if let Some((index, mask)) = Self::locate(point) {
+            match color {
+                BinaryColor::On => self.buffer[index] &= !mask,
+                BinaryColor::Off => self.buffer[index] |= mask,
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn row_bytes(&self) -> usize {
+        ROW_BYTES
+    }
+
+    fn locate(point: Point) -> Option<(usize, u8)> {
+        let (x, y) = (point.x, point.y);
+
+        if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 {
+            return None;
+        }
+
+        let index = y as usize * ROW_BYTES + x as usize / 8;
+        let mask = 0x80 >> (x as usize % 8);
+
+        Some((index, mask))
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.set_pixel(point, color);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+//! Errors from fetching and decoding an HTTP API, detailed enough to tell a
+//! dead network from a server that answered with something we can't use,
+//! and the checks that decide whether a response is worth reading.
+
+use heapless::String;
+use serde::Deserialize;
+
+use crate::tls::is_https;
+
+/// Longest URL we'll request or follow a redirect to
+pub const MAX_URL_LEN: usize = 512;
+
+/// Redirects followed before giving up
+pub const MAX_REDIRECTS: usize = 3;
+
+pub type Url = String<MAX_URL_LEN>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HttpError {
+    /// Host name didn't resolve
+    Dns,
+    /// TCP connect or the request write failed
+    Connect,
+    /// No complete response within the request timeout
+    Timeout,
+    /// The configured URL couldn't be parsed
+    InvalidUrl,
+    /// TLS handshake failed, or HTTPS was needed without the `tls` feature
+    Tls,
+    /// Response headers weren't valid HTTP
+    Malformed,
+    /// Server answered with a non-2xx status
+    Status(u16),
+    /// Redirect without a usable `Location`, or from HTTPS to plain HTTP
+    BadRedirect,
+    /// Still being redirected after `MAX_REDIRECTS` hops
+    TooManyRedirects,
+    /// Body isn't the media type we asked for, e.g. an HTML error page
+    ContentType,
+    /// Response didn't fit in the receive buffer
+    BufferTooSmall,
+    /// Connection closed before the whole body arrived
+    Truncated,
+    /// Body wasn't the JSON we expected
+    Parse,
+}
+
+/// Checks the status and headers of the response to a request for `url`
+/// before any of the body is read. `capacity` is the receive buffer size and
+/// `media_type` what the body must be, e.g. `application/json`. A missing
+/// `Content-Type` or `Content-Length` is let through, and caught later by
+/// the body decoder or the buffer size.
+///
+/// Returns the URL to request instead for a redirect, or `None` when the
+/// body should be read. A redirect from HTTPS to plain HTTP is refused, so a
+/// request that starts out encrypted stays that way.
+pub fn check_response<'h>(
+    url: &str,
+    status: u16,
+    headers: impl IntoIterator<Item = (&'h str, &'h [u8])>,
+    capacity: usize,
+    media_type: &str,
+) -> Result<Option<Url>, HttpError> {
+    let mut location = None;
+    let mut content_type = None;
+    let mut content_length = None;
+
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case("location") {
+            location = Some(value);
+        } else if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value);
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = Some(value);
+        }
+    }
+
+    match status {
+        200..=299 => {}
+        301 | 302 | 303 | 307 | 308 => {
+            let location = location
+                .and_then(|l| core::str::from_utf8(l).ok())
+                .ok_or(HttpError::BadRedirect)?;
+
+            let next = resolve(url, location.trim())?;
+            if is_https(url) && !is_https(&next) {
+                return Err(HttpError::BadRedirect);
+            }
+
+            return Ok(Some(next));
+        }
+        code => return Err(HttpError::Status(code)),
+    }
+
+    if let Some(value) = content_type
+        && !matches_media_type(value, media_type)
+    {
+        return Err(HttpError::ContentType);
+    }
+
+    let length = content_length
+        .and_then(|l| core::str::from_utf8(l).ok())
+        .and_then(|l| l.trim().parse::<usize>().ok());
+
+    if length.is_some_and(|l| l > capacity) {
+        return Err(HttpError::BufferTooSmall);
+    }
+
+    Ok(None)
+}
+
+/// `application/json; charset=utf-8` matches `application/json`
+fn matches_media_type(header: &[u8], media_type: &str) -> bool {
+    let essence = header.split(|b| *b == b';').next().unwrap_or_default();
+
+    essence
+        .trim_ascii()
+        .eq_ignore_ascii_case(media_type.as_bytes())
+}
+
+/// Resolves a `Location` header against the URL that returned it
+pub fn resolve(base: &str, location: &str) -> Result<Url, HttpError> {
+    let (scheme, rest) = base.split_once("://").ok_or(HttpError::InvalidUrl)?;
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    let origin = &base[..scheme.len() + 3 + authority_end];
+
+    let mut url = Url::new();
+    let push = |url: &mut Url, part: &str| url.push_str(part).map_err(|_| HttpError::BadRedirect);
+
+    if location.is_empty() {
+        return Err(HttpError::BadRedirect);
+    } else if location.contains("://") {
+        push(&mut url, location)?;
+    } else if location.starts_with("//") {
+        push(&mut url, scheme)?;
+        push(&mut url, ":")?;
+        push(&mut url, location)?;
+    } else if location.starts_with('/') {
+        push(&mut url, origin)?;
+        push(&mut url, location)?;
+    } else {
+        // Relative to the directory of the current path, ignoring the query
+        let path = &rest[authority_end..];
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+
+        push(&mut url, origin)?;
+        push(&mut url, if dir.is_empty() { "/" } else { dir })?;
+        push(&mut url, location)?;
+    }
+
+    Ok(url)
+}
+
+/// Where a request for a URL goes, for opening the connection ourselves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target<'a> {
+    pub https: bool,
+    pub host: &'a str,
+    pub port: u16,
+    /// Path and query, `/` if the URL has neither
+    pub path: &'a str,
+}
+
+impl<'a> Target<'a> {
+    /// Splits an `http://` or `https://` URL, dropping any fragment
+    pub fn parse(url: &'a str) -> Result<Self, HttpError> {
+        let (scheme, rest) = url.split_once("://").ok_or(HttpError::InvalidUrl)?;
+        let https = match scheme {
+            s if s.eq_ignore_ascii_case("http") => false,
+            s if s.eq_ignore_ascii_case("https") => true,
+            _ => return Err(HttpError::InvalidUrl),
+        };
+
+        let rest = rest.split('#').next().unwrap_or_default();
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(authority_end);
+
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| HttpError::InvalidUrl)?),
+            None => (authority, if https { 443 } else { 80 }),
+        };
+
+        if host.is_empty() {
+            return Err(HttpError::InvalidUrl);
+        }
+
+        let path = match path {
+            "" => "/",
+            // Only the query, e.g. `http://example.com?a=1`
+            _ if path.starts_with('?') => return Err(HttpError::InvalidUrl),
+            _ => path,
+        };
+
+        Ok(Self {
+            https,
+            host,
+            port,
+            path,
+        })
+    }
+}
+
+/// Decodes a JSON body, telling a body that was cut short apart from one
+/// that is simply the wrong shape
+pub fn parse_json<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, HttpError> {
+    use serde_json_core::de::Error;
+
+    match serde_json_core::from_slice::<T>(body) {
+        Ok((value, _)) => Ok(value),
+        Err(
+            Error::EofWhileParsingList
+            | Error::EofWhileParsingObject
+            | Error::EofWhileParsingString
+            | Error::EofWhileParsingNumber
+            | Error::EofWhileParsingValue,
+        ) => Err(HttpError::Truncated),
+        Err(_) => Err(HttpError::Parse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::OpenMeteoResponse;
+
+    const BODY: &[u8] =
+        br#"{"current":{"temperature":27.4,"weathercode":2,"relative_humidity_2m":74,"is_day":1}}"#;
+
+    const URL: &str = "http://api.example.com/v1/forecast?latitude=1";
+    const JSON: &str = "application/json";
+
+    fn check<'h>(status: u16, headers: &[(&'h str, &'h [u8])]) -> Result<Option<Url>, HttpError> {
+        check_response(URL, status, headers.iter().copied(), 1024, JSON)
+    }
+
+    #[test]
+    fn accepts_json() {
+        assert_eq!(
+            check(200, &[("Content-Type", b"application/json; charset=utf-8")]),
+            Ok(None)
+        );
+        assert_eq!(check(200, &[]), Ok(None));
+    }
+
+    #[test]
+    fn rejects_error_status_and_pages() {
+        assert_eq!(check(429, &[]), Err(HttpError::Status(429)));
+        assert_eq!(check(500, &[]), Err(HttpError::Status(500)));
+        assert_eq!(check(304, &[]), Err(HttpError::Status(304)));
+        assert_eq!(
+            check(200, &[("content-type", b"text/html")]),
+            Err(HttpError::ContentType)
+        );
+    }
+
+    #[test]
+    fn rejects_bodies_larger_than_buffer() {
+        assert_eq!(check(200, &[("Content-Length", b"1024")]), Ok(None));
+        assert_eq!(
+            check(200, &[("Content-Length", b"1025")]),
+            Err(HttpError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn follows_redirects() {
+        let redirect = |location: &'static [u8]| check(301, &[("Location", location)]);
+
+        assert_eq!(
+            redirect(b"https://other.example.com/x"),
+            Ok(Some("https://other.example.com/x".try_into().unwrap()))
+        );
+        assert_eq!(
+            redirect(b"/v2/forecast"),
+            Ok(Some(
+                "http://api.example.com/v2/forecast".try_into().unwrap()
+            ))
+        );
+        assert_eq!(
+            redirect(b"//cdn.example.com/f"),
+            Ok(Some("http://cdn.example.com/f".try_into().unwrap()))
+        );
+        assert_eq!(
+            redirect(b"archive?latitude=1"),
+            Ok(Some(
+                "http://api.example.com/v1/archive?latitude=1"
+                    .try_into()
+                    .unwrap()
+            ))
+        );
+        assert_eq!(check(302, &[]), Err(HttpError::BadRedirect));
+    }
+
+    #[test]
+    fn refuses_https_downgrade() {
+        let https = "https://api.example.com/v1/forecast";
+        let redirect = |location: &'static [u8]| {
+            check_response(https, 302, [("Location", location)], 1024, JSON)
+        };
+
+        assert_eq!(
+            redirect(b"http://api.example.com/v1/forecast"),
+            Err(HttpError::BadRedirect)
+        );
+        assert_eq!(
+            redirect(b"//cdn.example.com/f"),
+            Ok(Some("https://cdn.example.com/f".try_into().unwrap()))
+        );
+        assert_eq!(
+            redirect(b"HTTPS://other.example.com/x"),
+            Ok(Some("HTTPS://other.example.com/x".try_into().unwrap()))
+        );
+    }
+
+    #[test]
+    fn resolves_against_bare_host() {
+        assert_eq!(
+            resolve("http://example.com", "a").unwrap(),
+            "http://example.com/a"
+        );
+        assert_eq!(resolve("example.com/a", "b"), Err(HttpError::InvalidUrl));
+    }
+
+    #[test]
+    fn splits_urls() {
+        assert_eq!(
+            Target::parse(URL),
+            Ok(Target {
+                https: false,
+                host: "api.example.com",
+                port: 80,
+                path: "/v1/forecast?latitude=1",
+            })
+        );
+        assert_eq!(
+            Target::parse("HTTPS://localhost:8443"),
+            Ok(Target {
+                https: true,
+                host: "localhost",
+                port: 8443,
+                path: "/",
+            })
+        );
+        assert_eq!(
+            Target::parse("https://example.com/a#top").map(|t| (t.port, t.path)),
+            Ok((443, "/a"))
+        );
+
+        for url in [
+            "example.com/a",
+            "ftp://example.com/a",
+            "http:///a",
+            "http://example.com:port/a",
+            "http://example.com?a=1",
+        ] {
+            assert_eq!(Target::parse(url), Err(HttpError::InvalidUrl), "{url}");
+        }
+    }
+
+    #[test]
+    fn parses_complete_body() {
+        let response = parse_json::<OpenMeteoResponse>(BODY).unwrap();
+
+        assert_eq!(response.current.weathercode, 2);
+    }
+
+    #[test]
+    fn cut_off_body_is_truncated() {
+        assert_eq!(
+            parse_json::<OpenMeteoResponse>(&BODY[..40]).err(),
+            Some(HttpError::Truncated)
+        );
+    }
+
+    #[test]
+    fn wrong_shape_is_parse_error() {
+        assert_eq!(
+            parse_json::<OpenMeteoResponse>(br#"{"error":true,"reason":"bad"}"#).err(),
+            Some(HttpError::Parse)
+        );
+        assert_eq!(
+            parse_json::<OpenMeteoResponse>(b"<html>").err(),
+            Some(HttpError::Parse)
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! 1-bit weather icons, embedded from `images/icons` at build time. Each one
+//! is an `ICON_SIZE` square BMP with the same palette as the badge images.
+
+pub const ICON_SIZE: u32 = 20;
+
+static CLEAR_DAY: &[u8] = include_bytes!("../../images/icons/clear_day.bmp");
+static CLEAR_NIGHT: &[u8] = include_bytes!("../../images/icons/clear_night.bmp");
+static PARTLY_DAY: &[u8] = include_bytes!("../../images/icons/partly_day.bmp");
+static PARTLY_NIGHT: &[u8] = include_bytes!("../../images/icons/partly_night.bmp");
+static CLOUD: &[u8] = include_bytes!("../../images/icons/cloud.bmp");
+static FOG: &[u8] = include_bytes!("../../images/icons/fog.bmp");
+static RAIN: &[u8] = include_bytes!("../../images/icons/rain.bmp");
+static SNOW: &[u8] = include_bytes!("../../images/icons/snow.bmp");
+static THUNDER: &[u8] = include_bytes!("../../images/icons/thunder.bmp");
+
+/// Icon for a WMO weather code, or `None` for codes we don't know, which
+/// are left to the text description
+pub fn weather_icon(code: u8, is_day: bool) -> Option<&'static [u8]> {
+    let icon = match (code, is_day) {
+        (0 | 1, true) => CLEAR_DAY,
+        (0 | 1, false) => CLEAR_NIGHT,
+        (2, true) => PARTLY_DAY,
+        (2, false) => PARTLY_NIGHT,
+        (3, _) => CLOUD,
+        (45..=48, _) => FOG,
+        (51..=67 | 80..=82, _) => RAIN,
+        (71..=77 | 85 | 86, _) => SNOW,
+        (95..=99, _) => THUNDER,
+        _ => return None,
+    };
+
+    Some(icon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day_and_night_variants() {
+        assert_ne!(weather_icon(0, true), weather_icon(0, false));
+        assert_ne!(weather_icon(2, true), weather_icon(2, false));
+        assert_eq!(weather_icon(63, true), weather_icon(63, false));
+    }
+
+    #[test]
+    fn unknown_codes_have_no_icon() {
+        assert!(weather_icon(4, true).is_none());
+        assert!(weather_icon(255, false).is_none());
+        assert!(weather_icon(99, true).is_some());
+    }
+}
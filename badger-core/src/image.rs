@@ -0,0 +1,53 @@
+pub static IMAGES: [&[u8]; 3] = [
+    include_bytes!("../../images/julian.bmp"),
+    include_bytes!("../../images/tropical.bmp"),
+    include_bytes!("../../images/2026.bmp"),
+];
+
+pub fn get_position() -> (i32, i32) {
+    (0, 24)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Shift {
+    None,
+    Next,
+    Prev,
+}
+
+/// Index of the image `dir` steps away from `index`, wrapping at both ends
+pub fn shifted(index: usize, dir: Shift) -> usize {
+    let index = clamp(index);
+
+    match dir {
+        Shift::Next => (index + 1) % IMAGES.len(),
+        Shift::Prev => (if index == 0 { IMAGES.len() } else { index }) - 1,
+        Shift::None => index,
+    }
+}
+
+pub fn clamp(index: usize) -> usize {
+    index.clamp(0, IMAGES.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_wraps_around() {
+        let last = IMAGES.len() - 1;
+
+        assert_eq!(shifted(last, Shift::Next), 0);
+        assert_eq!(shifted(0, Shift::Prev), last);
+        assert_eq!(shifted(0, Shift::Next), 1);
+        assert_eq!(shifted(1, Shift::None), 1);
+    }
+
+    #[test]
+    fn out_of_range_index_is_clamped() {
+        assert_eq!(clamp(200), IMAGES.len() - 1);
+        assert_eq!(shifted(200, Shift::None), IMAGES.len() - 1);
+    }
+}
@@ -0,0 +1,872 @@
+use embedded_graphics::{
+    image::Image,
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, Polyline, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle},
+    text::Text,
+};
+use heapless::{String, Vec};
+use time::PrimitiveDateTime;
+use tinybmp::Bmp;
+use u8g2_fonts::{
+    U8g2TextStyle,
+    fonts::{u8g2_font_6x10_tf, u8g2_font_battery19_tn, u8g2_font_lastapprenticebold_tr},
+};
+
+use crate::{
+    air::{AirQuality, aqi_category},
+    battery::BatteryState,
+    helpers::easy_format,
+    icons::{ICON_SIZE, weather_icon},
+    image,
+    provision::{AP_SSID, SETUP_URL},
+    sync::SyncError,
+    units::Units,
+    weather::{
+        CurrentWeather, FORECAST_HOURS, Forecast, Freshness, HourlyForecast, compass_point,
+        short_description, weather_description, weekday_name,
+    },
+};
+
+// UC8151 panel geometry
+pub const WIDTH: u32 = 296;
+pub const HEIGHT: u32 = 128;
+
+const TOP_BAR_HEIGHT: u32 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Screen {
+    None,
+    TopBar,
+    Image,
+    Forecast,
+    Hourly,
+    Details,
+    AirQuality,
+    Full,
+    /// Instructions for joining the setup access point
+    Provisioning,
+    Shutdown,
+}
+
+impl Screen {
+    pub const ALL: [Screen; 10] = [
+        Screen::None,
+        Screen::TopBar,
+        Screen::Image,
+        Screen::Forecast,
+        Screen::Hourly,
+        Screen::Details,
+        Screen::AirQuality,
+        Screen::Full,
+        Screen::Provisioning,
+        Screen::Shutdown,
+    ];
+}
+
+/// What fills the area below the top bar
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Page {
+    #[default]
+    Image,
+    Forecast,
+    Hourly,
+    /// Current conditions that don't fit in the top bar
+    Details,
+    AirQuality,
+}
+
+impl Page {
+    /// The page button C goes to from this one
+    pub fn next(self) -> Page {
+        match self {
+            Page::Image => Page::Forecast,
+            Page::Forecast => Page::Hourly,
+            Page::Hourly => Page::Details,
+            Page::Details => Page::AirQuality,
+            Page::AirQuality => Page::Image,
+        }
+    }
+
+    /// Screen that redraws just this page
+    pub fn screen(self) -> Screen {
+        match self {
+            Page::Image => Screen::Image,
+            Page::Forecast => Screen::Forecast,
+            Page::Hourly => Screen::Hourly,
+            Page::Details => Screen::Details,
+            Page::AirQuality => Screen::AirQuality,
+        }
+    }
+}
+
+/// Snapshot of everything the layout reads when drawing a frame
+#[derive(Clone)]
+pub struct Badge<'a> {
+    pub weather: Option<CurrentWeather>,
+    /// How old `weather` is, which can hide it altogether
+    pub weather_freshness: Freshness,
+    pub forecast: Option<Forecast>,
+    pub hourly: Option<HourlyForecast>,
+    pub air_quality: Option<AirQuality>,
+    /// US AQI above which the top bar shows a warning
+    pub aqi_warning: u16,
+    pub time: Option<PrimitiveDateTime>,
+    pub power: Option<BatteryState>,
+    /// Shown in place of the weather description until a sync succeeds,
+    /// except for a failed air quality fetch, which only the air quality
+    /// page mentions
+    pub sync_error: Option<SyncError>,
+    pub image: &'a [u8],
+    /// Drawn below the top bar on a full refresh
+    pub page: Page,
+    /// What the weather and forecasts were fetched in
+    pub units: Units,
+}
+
+impl Badge<'_> {
+    /// The current weather, unless it is too old to show
+    pub fn current_weather(&self) -> Option<&CurrentWeather> {
+        self.weather
+            .as_ref()
+            .filter(|_| self.weather_freshness != Freshness::Expired)
+    }
+}
+
+/// Draws `screen` and returns the region of the panel that changed, or
+/// `None` if there is nothing to refresh
+pub fn draw_screen<D>(
+    display: &mut D,
+    screen: Screen,
+    badge: &Badge,
+) -> Result<Option<Rectangle>, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let region = match screen {
+        Screen::Full => draw_badge(display, badge)?,
+        Screen::TopBar => draw_top_bar(display, badge)?,
+        Screen::Image => draw_current_image(display, badge.image)?,
+        Screen::Forecast => draw_forecast(display, badge.forecast.as_ref(), badge.units)?,
+        Screen::Hourly => draw_hourly(display, badge.hourly.as_ref(), badge.units)?,
+        Screen::Details => draw_details(display, badge.current_weather(), badge.units)?,
+        Screen::AirQuality => draw_air_quality(
+            display,
+            badge.air_quality.as_ref(),
+            badge.sync_error.as_ref(),
+        )?,
+        Screen::Provisioning => draw_provisioning(display)?,
+        Screen::None | Screen::Shutdown => return Ok(None),
+    };
+
+    Ok(Some(region))
+}
+
+/// Draws the temperature and humidity on the left and the conditions in the
+/// middle. Stale weather has its age in place of the humidity. A sync error
+/// takes the place of the conditions, and so does `air_alert`, an AQI worth
+/// warning about, shown in white on black.
+pub fn draw_weather<D>(
+    display: &mut D,
+    weather: Option<&CurrentWeather>,
+    freshness: Freshness,
+    sync_error: Option<&SyncError>,
+    air_alert: Option<u16>,
+    units: Units,
+) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+    let alert_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::On);
+
+    if let Some(data) = weather {
+        let symbol = units.temperature_symbol();
+        let top_text: String<64> = match freshness {
+            Freshness::Stale(hours) if hours >= 48 => easy_format::<64>(format_args!(
+                "{:.0}{symbol} | {}d ago",
+                data.temperature,
+                hours / 24
+            )),
+            Freshness::Stale(hours) => easy_format::<64>(format_args!(
+                "{:.0}{symbol} | {hours}h ago",
+                data.temperature
+            )),
+            _ => easy_format::<64>(format_args!(
+                "{:.0}{symbol} | {:.0}%",
+                data.temperature, data.relative_humidity_2m
+            )),
+        };
+
+        let text = Text::new(top_text.as_str(), Point::new(8, 17), &character_style);
+        text.draw(display)?;
+    }
+
+    let label: String<16>;
+    let (center_text, icon, alert) = match (sync_error, air_alert, weather) {
+        (Some(error), _, _) => {
+            label = error.label();
+            (label.as_str(), None, false)
+        }
+        (None, Some(aqi), _) => {
+            label = easy_format::<16>(format_args!("AQI {aqi}"));
+            (label.as_str(), None, true)
+        }
+        (None, None, Some(data)) => (
+            weather_description(data.weathercode),
+            weather_icon(data.weathercode, data.is_daytime()),
+            false,
+        ),
+        (None, None, None) => return Ok(Rectangle::zero()),
+    };
+
+    if alert {
+        let text = Text::new(center_text, Point::new(0, 17), &alert_style);
+        let text = text.translate(Point::new(
+            ((WIDTH / 2) as i32) - text.bounding_box().center().x,
+            0,
+        ));
+        let background = text.bounding_box().offset(3);
+
+        background
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+            .draw(display)?;
+        text.draw(display)?;
+
+        return Ok(background);
+    }
+
+    let text = Text::new(center_text, Point::new(0, 17), &character_style);
+
+    // The icon sits left of the description, and the pair is centered
+    let icon_width = icon.map_or(0, |_| ICON_SIZE as i32 + 4);
+    let width = icon_width + text.bounding_box().size.width as i32;
+    let left = ((WIDTH / 2) as i32) - width / 2;
+
+    let text = text.translate(Point::new(
+        left + icon_width - text.bounding_box().top_left.x,
+        0,
+    ));
+    text.draw(display)?;
+
+    let Some(icon) = icon else {
+        return Ok(text.bounding_box());
+    };
+
+    let icon = draw_icon(
+        display,
+        icon,
+        Point::new(left, ((TOP_BAR_HEIGHT - ICON_SIZE) / 2) as i32),
+    )?;
+
+    Ok(Rectangle::with_corners(
+        icon.top_left,
+        text.bounding_box().bottom_right().unwrap_or(icon.top_left),
+    ))
+}
+
+/// Draws one of the [`crate::icons`] with its top left corner at `position`
+fn draw_icon<D>(display: &mut D, icon: &[u8], position: Point) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let bmp: Bmp<BinaryColor> = Bmp::from_slice(icon).unwrap();
+    let image = Image::new(&bmp, position);
+
+    image.draw(display)?;
+
+    Ok(image.bounding_box())
+}
+
+pub fn draw_time<D>(
+    display: &mut D,
+    time: Option<PrimitiveDateTime>,
+    power: Option<BatteryState>,
+) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+    let battery_style = U8g2TextStyle::new(u8g2_font_battery19_tn, BinaryColor::Off);
+
+    if let Some(when) = time {
+        let str = get_display_time(when);
+
+        let text = Text::new(
+            str.as_str(),
+            Point::new((WIDTH - 62) as i32, 17),
+            character_style,
+        );
+
+        text.draw(display)?;
+    }
+
+    let text = Text::new(
+        battery_glyph(power),
+        Point::new((WIDTH - 12) as i32, 20),
+        battery_style,
+    );
+
+    text.draw(display)?;
+
+    Ok(Rectangle::new(
+        Point::new(192, 0),
+        Size::new(WIDTH - 192, TOP_BAR_HEIGHT),
+    ))
+}
+
+pub fn draw_top_bar<D>(display: &mut D, badge: &Badge) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let top_bounds = Rectangle::new(Point::new(0, 0), Size::new(WIDTH, TOP_BAR_HEIGHT));
+
+    top_bounds
+        .into_styled(
+            PrimitiveStyleBuilder::default()
+                .stroke_color(BinaryColor::Off)
+                .fill_color(BinaryColor::On)
+                .stroke_width(1)
+                .build(),
+        )
+        .draw(display)?;
+
+    draw_weather(
+        display,
+        badge.current_weather(),
+        badge.weather_freshness,
+        badge.sync_error.as_ref().filter(|e| !e.is_partial()),
+        badge
+            .air_quality
+            .and_then(|air| air.warning(badge.aqi_warning)),
+        badge.units,
+    )?;
+    draw_time(display, badge.time, badge.power)?;
+
+    Ok(top_bounds)
+}
+
+pub fn draw_current_image<D>(display: &mut D, current_image: &[u8]) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let position = image::get_position();
+
+    let bmp: Bmp<BinaryColor> = Bmp::from_slice(current_image).unwrap();
+    let image = Image::new(&bmp, position.into());
+
+    // clear image location by writing a white rectangle over previous image location
+    let clear_rectangle = Rectangle::new(
+        Point::new(0, TOP_BAR_HEIGHT as i32),
+        Size::new(WIDTH, HEIGHT - TOP_BAR_HEIGHT),
+    );
+    clear_rectangle
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    image.draw(display)?;
+
+    Ok(clear_rectangle)
+}
+
+/// Draws one column per day in the image area, or a placeholder until the
+/// first forecast arrives
+pub fn draw_forecast<D>(
+    display: &mut D,
+    forecast: Option<&Forecast>,
+    units: Units,
+) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+
+    let area = Rectangle::new(
+        Point::new(0, TOP_BAR_HEIGHT as i32),
+        Size::new(WIDTH, HEIGHT - TOP_BAR_HEIGHT),
+    );
+    area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    let days = match forecast {
+        Some(days) if !days.is_empty() => days,
+        _ => {
+            let text = Text::new("No forecast yet", Point::new(0, 80), &character_style);
+            let center = ((WIDTH / 2) as i32) - text.bounding_box().center().x;
+            text.translate(Point::new(center, 0)).draw(display)?;
+
+            return Ok(area);
+        }
+    };
+
+    let column_width = (WIDTH / days.len() as u32) as i32;
+    let separator = PrimitiveStyle::with_stroke(BinaryColor::Off, 1);
+
+    for (i, day) in days.iter().enumerate() {
+        let left = i as i32 * column_width;
+
+        if i > 0 {
+            Line::new(Point::new(left, 30), Point::new(left, HEIGHT as i32 - 6))
+                .into_styled(separator)
+                .draw(display)?;
+        }
+
+        let temperatures = easy_format::<16>(format_args!(
+            "{:.0}/{:.0}{}",
+            day.high,
+            day.low,
+            units.temperature_symbol()
+        ));
+        let precipitation = match day.precipitation_probability {
+            Some(chance) => easy_format::<16>(format_args!("{chance}%")),
+            None => easy_format::<16>(format_args!("-")),
+        };
+
+        // Forecasts are for the whole day, so always the day icon
+        let icon = weather_icon(day.weathercode, true);
+        let description = match icon {
+            Some(icon) => {
+                let x = left + column_width / 2 - ICON_SIZE as i32 / 2;
+                draw_icon(display, icon, Point::new(x, 52))?;
+                ""
+            }
+            None => short_description(day.weathercode),
+        };
+
+        let rows = [
+            (weekday_name(day.weekday), 46),
+            (description, 68),
+            (temperatures.as_str(), 90),
+            (precipitation.as_str(), 112),
+        ];
+
+        for (line, y) in rows {
+            let text = Text::new(line, Point::new(0, y), &character_style);
+            let center = left + column_width / 2 - text.bounding_box().center().x;
+            text.translate(Point::new(center, 0)).draw(display)?;
+        }
+    }
+
+    Ok(area)
+}
+
+/// Plots the hourly temperature as a line over outlined precipitation
+/// probability bars, with the temperature range on the left and every sixth
+/// hour along the bottom
+pub fn draw_hourly<D>(
+    display: &mut D,
+    hourly: Option<&HourlyForecast>,
+    units: Units,
+) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let label_style = U8g2TextStyle::new(u8g2_font_6x10_tf, BinaryColor::Off);
+
+    let area = Rectangle::new(
+        Point::new(0, TOP_BAR_HEIGHT as i32),
+        Size::new(WIDTH, HEIGHT - TOP_BAR_HEIGHT),
+    );
+    area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    let hours = match hourly {
+        Some(hourly) if !hourly.temperature.is_empty() => hourly,
+        _ => {
+            let character_style =
+                U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+            let text = Text::new(
+                "No hourly forecast yet",
+                Point::new(0, 80),
+                &character_style,
+            );
+            let center = ((WIDTH / 2) as i32) - text.bounding_box().center().x;
+            text.translate(Point::new(center, 0)).draw(display)?;
+
+            return Ok(area);
+        }
+    };
+
+    // Plot area, leaving room for the labels
+    let (left, top, bottom) = (30, 32, HEIGHT as i32 - 16);
+    let height = bottom - top;
+    let step = (WIDTH as i32 - 4 - left) / hours.temperature.len() as i32;
+    let axis = PrimitiveStyle::with_stroke(BinaryColor::Off, 1);
+
+    for (i, &chance) in hours.precipitation_probability.iter().enumerate() {
+        let bar = height * chance.min(100) as i32 / 100;
+
+        if bar > 0 {
+            Rectangle::new(
+                Point::new(left + i as i32 * step + 1, bottom - bar),
+                Size::new((step - 2) as u32, bar as u32),
+            )
+            .into_styled(axis)
+            .draw(display)?;
+        }
+    }
+
+    let (min, max) = hours
+        .temperature
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), &t| {
+            (min.min(t), max.max(t))
+        });
+    let range = (max - min).max(1.0);
+
+    let line: Vec<Point, FORECAST_HOURS> = hours
+        .temperature
+        .iter()
+        .enumerate()
+        .map(|(i, &t)| {
+            let x = left + i as i32 * step + step / 2;
+            let y = bottom - ((t - min) / range * height as f32) as i32;
+            Point::new(x, y)
+        })
+        .collect();
+
+    Polyline::new(&line)
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 2))
+        .draw(display)?;
+
+    Line::new(Point::new(left, top), Point::new(left, bottom))
+        .into_styled(axis)
+        .draw(display)?;
+    Line::new(
+        Point::new(left, bottom),
+        Point::new(WIDTH as i32 - 4, bottom),
+    )
+    .into_styled(axis)
+    .draw(display)?;
+
+    // Room for any f32, as a provider can send absurd values and they stay
+    // cached until the next sync
+    let symbol = units.temperature_symbol();
+    let max_label = easy_format::<64>(format_args!("{max:.0}{symbol}"));
+    let min_label = easy_format::<64>(format_args!("{min:.0}{symbol}"));
+
+    for (label, y) in [(max_label, top + 7), (min_label, bottom)] {
+        let text = Text::new(label.as_str(), Point::new(0, y), &label_style);
+        let right = left - 3 - text.bounding_box().size.width as i32;
+        text.translate(Point::new(right, 0)).draw(display)?;
+    }
+
+    for (i, point) in line.iter().enumerate().step_by(6) {
+        let hour = (hours.start_hour as usize + i) % 24;
+        let (hour, suffix) = twelve_hour(hour as u8);
+        let label = easy_format::<8>(format_args!("{hour}{suffix}"));
+
+        let text = Text::new(
+            label.as_str(),
+            Point::new(0, HEIGHT as i32 - 4),
+            &label_style,
+        );
+        let center = point.x - text.bounding_box().center().x;
+        text.translate(Point::new(center, 0)).draw(display)?;
+    }
+
+    Ok(area)
+}
+
+/// Lists the current conditions in two columns: temperature, humidity and
+/// precipitation on the left, wind and UV on the right. Values the provider
+/// doesn't report show as `-`.
+pub fn draw_details<D>(
+    display: &mut D,
+    weather: Option<&CurrentWeather>,
+    units: Units,
+) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+
+    let area = Rectangle::new(
+        Point::new(0, TOP_BAR_HEIGHT as i32),
+        Size::new(WIDTH, HEIGHT - TOP_BAR_HEIGHT),
+    );
+    area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    let Some(data) = weather else {
+        let text = Text::new("No weather yet", Point::new(0, 80), &character_style);
+        let center = ((WIDTH / 2) as i32) - text.bounding_box().center().x;
+        text.translate(Point::new(center, 0)).draw(display)?;
+
+        return Ok(area);
+    };
+
+    let missing = || easy_format::<24>(format_args!("-"));
+    let temperature = units.temperature_symbol();
+
+    let rows = [
+        (
+            "Temp",
+            easy_format::<24>(format_args!("{:.1}{temperature}", data.temperature)),
+            "Wind",
+            data.wind_speed_10m.map_or_else(missing, |speed| {
+                easy_format::<24>(format_args!("{speed:.0} {}", units.wind_speed_symbol()))
+            }),
+        ),
+        (
+            "Feels like",
+            data.apparent_temperature.map_or_else(missing, |t| {
+                easy_format::<24>(format_args!("{t:.1}{temperature}"))
+            }),
+            "From",
+            data.wind_direction_10m.map_or_else(missing, |degrees| {
+                easy_format::<24>(format_args!("{}", compass_point(degrees)))
+            }),
+        ),
+        (
+            "Humidity",
+            easy_format::<24>(format_args!("{:.0}%", data.relative_humidity_2m)),
+            "UV",
+            data.uv_index
+                .map_or_else(missing, |uv| easy_format::<24>(format_args!("{uv:.0}"))),
+        ),
+        (
+            "Rain",
+            data.precipitation.map_or_else(missing, |amount| {
+                easy_format::<24>(format_args!("{amount:.1} {}", units.precipitation_symbol()))
+            }),
+            "",
+            easy_format::<24>(format_args!("")),
+        ),
+    ];
+
+    draw_rows(display, rows, [8, 96, 168, 216])?;
+
+    Ok(area)
+}
+
+/// Lists the US AQI, its category and PM2.5 on the left and the pollen
+/// counts on the right, with `-` for anything the API left out. A failed
+/// air quality fetch in `sync_error` is noted under the cached values.
+pub fn draw_air_quality<D>(
+    display: &mut D,
+    air_quality: Option<&AirQuality>,
+    sync_error: Option<&SyncError>,
+) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+
+    let area = Rectangle::new(
+        Point::new(0, TOP_BAR_HEIGHT as i32),
+        Size::new(WIDTH, HEIGHT - TOP_BAR_HEIGHT),
+    );
+    area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    // Only a failed air quality fetch concerns this page
+    let failed = sync_error.filter(|e| e.is_partial());
+
+    let Some(air) = air_quality else {
+        let label = failed.map(SyncError::label);
+        let message = label.as_ref().map_or("No air quality yet", |l| l.as_str());
+
+        let text = Text::new(message, Point::new(0, 80), &character_style);
+        let center = ((WIDTH / 2) as i32) - text.bounding_box().center().x;
+        text.translate(Point::new(center, 0)).draw(display)?;
+
+        return Ok(area);
+    };
+
+    let missing = || easy_format::<24>(format_args!("-"));
+    let empty = || easy_format::<24>(format_args!(""));
+    let pollen = |count: Option<f32>| {
+        count.map_or_else(missing, |count| {
+            easy_format::<24>(format_args!("{count:.0}"))
+        })
+    };
+
+    let rows = [
+        (
+            "US AQI",
+            air.aqi()
+                .map_or_else(missing, |aqi| easy_format::<24>(format_args!("{aqi}"))),
+            "Alder",
+            pollen(air.alder_pollen),
+        ),
+        (
+            air.aqi().map_or("", aqi_category),
+            empty(),
+            "Birch",
+            pollen(air.birch_pollen),
+        ),
+        (
+            "PM2.5",
+            air.pm2_5.map_or_else(missing, |pm| {
+                easy_format::<24>(format_args!("{pm:.0} ug/m3"))
+            }),
+            "Grass",
+            pollen(air.grass_pollen),
+        ),
+        (
+            failed.map_or("", |_| "Not updated"),
+            empty(),
+            "Ragweed",
+            pollen(air.ragweed_pollen),
+        ),
+    ];
+
+    draw_rows(display, rows, [8, 96, 168, 240])?;
+
+    Ok(area)
+}
+
+/// Tells the user how to reach the setup form, over the whole screen as
+/// nothing else is running meanwhile
+pub fn draw_provisioning<D>(display: &mut D) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+
+    let area = Rectangle::new(Point::zero(), Size::new(WIDTH, HEIGHT));
+    area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    let lines = [
+        (22, "WiFi setup"),
+        (50, "1. Join the network"),
+        (72, AP_SSID),
+        (94, "2. Then open"),
+        (116, SETUP_URL),
+    ];
+
+    for (y, line) in lines {
+        let text = Text::new(line, Point::new(0, y), &character_style);
+        let center = ((WIDTH / 2) as i32) - text.bounding_box().center().x;
+        text.translate(Point::new(center, 0)).draw(display)?;
+    }
+
+    Ok(area)
+}
+
+/// Draws label and value pairs in two columns, one row every 22 pixels
+/// below the top bar, starting each text at the matching x in `columns`
+fn draw_rows<D, const N: usize>(
+    display: &mut D,
+    rows: [(&str, String<24>, &str, String<24>); N],
+    columns: [i32; 4],
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let character_style = U8g2TextStyle::new(u8g2_font_lastapprenticebold_tr, BinaryColor::Off);
+
+    for (i, (left_label, left_value, right_label, right_value)) in rows.into_iter().enumerate() {
+        let y = 46 + i as i32 * 22;
+
+        for (x, text) in columns.into_iter().zip([
+            left_label,
+            left_value.as_str(),
+            right_label,
+            right_value.as_str(),
+        ]) {
+            Text::new(text, Point::new(x, y), &character_style).draw(display)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn draw_badge<D>(display: &mut D, badge: &Badge) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    draw_top_bar(display, badge)?;
+
+    match badge.page {
+        Page::Image => draw_current_image(display, badge.image)?,
+        Page::Forecast => draw_forecast(display, badge.forecast.as_ref(), badge.units)?,
+        Page::Hourly => draw_hourly(display, badge.hourly.as_ref(), badge.units)?,
+        Page::Details => draw_details(display, badge.current_weather(), badge.units)?,
+        Page::AirQuality => draw_air_quality(
+            display,
+            badge.air_quality.as_ref(),
+            badge.sync_error.as_ref(),
+        )?,
+    };
+
+    Ok(Rectangle::new(Point::zero(), Size::new(WIDTH, HEIGHT)))
+}
+
+pub fn get_display_time(time: PrimitiveDateTime) -> String<64> {
+    let (hour, am) = twelve_hour(time.hour());
+
+    easy_format::<64>(format_args!("  {}:{:02}{}", hour, time.minute(), am))
+}
+
+/// 1-12 and `A` or `P`
+fn twelve_hour(hour: u8) -> (u8, &'static str) {
+    match hour {
+        x if x > 12 => (x - 12, "P"),
+        12 => (12, "P"),
+        0 => (12, "A"),
+        x => (x, "A"),
+    }
+}
+
+fn battery_glyph(power: Option<BatteryState>) -> &'static str {
+    match power {
+        None => "7",
+        Some(BatteryState::Error) => "7",
+        Some(BatteryState::UsbPower) => "6",
+        Some(BatteryState::Battery(x)) if x > 90 => "5",
+        Some(BatteryState::Battery(x)) if x > 70 => "4",
+        Some(BatteryState::Battery(x)) if x > 50 => "3",
+        Some(BatteryState::Battery(x)) if x > 30 => "2",
+        Some(BatteryState::Battery(x)) if x > 10 => "1",
+        _ => "0",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month, Time};
+
+    fn at(hour: u8, minute: u8) -> PrimitiveDateTime {
+        PrimitiveDateTime::new(
+            Date::from_calendar_date(2026, Month::January, 1).unwrap(),
+            Time::from_hms(hour, minute, 0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn display_time_is_twelve_hour() {
+        assert_eq!(get_display_time(at(0, 0)), "  12:00A");
+        assert_eq!(get_display_time(at(9, 5)), "  9:05A");
+        assert_eq!(get_display_time(at(12, 0)), "  12:00P");
+        assert_eq!(get_display_time(at(23, 59)), "  11:59P");
+    }
+
+    #[test]
+    fn battery_glyph_thresholds() {
+        assert_eq!(battery_glyph(None), "7");
+        assert_eq!(battery_glyph(Some(BatteryState::UsbPower)), "6");
+        assert_eq!(battery_glyph(Some(BatteryState::Battery(100))), "5");
+        assert_eq!(battery_glyph(Some(BatteryState::Battery(90))), "4");
+        assert_eq!(battery_glyph(Some(BatteryState::Battery(10))), "0");
+    }
+
+    #[test]
+    fn hourly_labels_fit_any_temperature() {
+        let hourly = HourlyForecast {
+            start_hour: 0,
+            temperature: Vec::from_slice(&[f32::MIN, 123_456.0, f32::MAX]).unwrap(),
+            precipitation_probability: Vec::new(),
+        };
+
+        for units in [Units::Metric, Units::Imperial] {
+            let mut frame = crate::framebuffer::Framebuffer::new();
+            assert!(draw_hourly(&mut frame, Some(&hourly), units).is_ok());
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Hardware independent pieces of the badge: layout, data models and the
+//! logic that decides what to show. Everything here builds on the host so it
+//! can be exercised without flashing the device.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod air;
+pub mod api;
+pub mod battery;
+pub mod dhcp;
+pub mod dns;
+pub mod framebuffer;
+pub mod helpers;
+pub mod http;
+pub mod icons;
+pub mod image;
+pub mod layout;
+pub mod provider;
+pub mod provision;
+pub mod retry;
+pub mod schedule;
+pub mod sntp;
+pub mod storage;
+pub mod sync;
+pub mod tls;
+pub mod tz;
+pub mod units;
+pub mod wake;
+pub mod weather;
+pub mod wifi;
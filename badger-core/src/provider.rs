@@ -0,0 +1,416 @@
+//! Weather backends. Each one turns its API's response into the same
+//! [`WeatherReport`], with conditions as WMO weather codes and values in the
+//! configured [`Units`], so nothing past the fetch knows which service the
+//! data came from.
+
+use serde::Deserialize;
+
+use crate::{
+    api::OpenMeteoResponse,
+    http::{HttpError, parse_json},
+    units::Units,
+    weather::{CurrentWeather, WeatherReport},
+};
+
+/// WMO code for conditions a backend reports that we have no mapping for
+pub const UNKNOWN_CODE: u8 = 255;
+
+pub trait WeatherProvider {
+    /// URL to GET
+    fn url(&self) -> &str;
+
+    /// Whether the current conditions can be decoded from the start of the
+    /// body, for APIs that send much more than fits in the receive buffer
+    fn partial_body(&self) -> bool {
+        false
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<WeatherReport, HttpError>;
+}
+
+/// api.open-meteo.com, with `current=` and optionally `daily=` variables.
+/// The units are part of the URL.
+pub struct OpenMeteo<'a> {
+    pub url: &'a str,
+}
+
+/// api.met.no `locationforecast/2.0/compact`, HTTPS only. Always metric.
+pub struct MetNorway<'a> {
+    pub url: &'a str,
+    pub units: Units,
+}
+
+/// A self-hosted endpoint returning
+/// `{"temperature": 21.5, "humidity": 60, "code": 3}` in °C, % and WMO code,
+/// with an optional `"is_day": 0` for night icons
+pub struct GenericJson<'a> {
+    pub url: &'a str,
+    pub units: Units,
+}
+
+/// The backend picked by configuration
+pub enum Provider<'a> {
+    OpenMeteo(OpenMeteo<'a>),
+    MetNorway(MetNorway<'a>),
+    GenericJson(GenericJson<'a>),
+}
+
+impl<'a> Provider<'a> {
+    /// `kind` is `open-meteo`, `met-no` or `json`. Backends that can't be
+    /// asked for `units` convert to them.
+    pub fn from_config(kind: &str, url: &'a str, units: Units) -> Option<Self> {
+        match kind.trim() {
+            "open-meteo" => Some(Provider::OpenMeteo(OpenMeteo { url })),
+            "met-no" => Some(Provider::MetNorway(MetNorway { url, units })),
+            "json" => Some(Provider::GenericJson(GenericJson { url, units })),
+            _ => None,
+        }
+    }
+
+    fn inner(&self) -> &dyn WeatherProvider {
+        match self {
+            Provider::OpenMeteo(p) => p,
+            Provider::MetNorway(p) => p,
+            Provider::GenericJson(p) => p,
+        }
+    }
+}
+
+impl WeatherProvider for Provider<'_> {
+    fn url(&self) -> &str {
+        self.inner().url()
+    }
+
+    fn partial_body(&self) -> bool {
+        self.inner().partial_body()
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<WeatherReport, HttpError> {
+        self.inner().parse(body)
+    }
+}
+
+impl WeatherProvider for OpenMeteo<'_> {
+    fn url(&self) -> &str {
+        self.url
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<WeatherReport, HttpError> {
+        let response = parse_json::<OpenMeteoResponse>(body)?;
+
+        Ok(WeatherReport {
+            current: response.current,
+            forecast: response.daily.map(|daily| daily.forecast()),
+            hourly: response.hourly.and_then(|hourly| hourly.forecast()),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct MetEntry<'a> {
+    #[serde(borrow)]
+    data: MetData<'a>,
+}
+
+#[derive(Deserialize)]
+struct MetData<'a> {
+    instant: MetInstant,
+    #[serde(borrow)]
+    next_1_hours: Option<MetPeriod<'a>>,
+    #[serde(borrow)]
+    next_6_hours: Option<MetPeriod<'a>>,
+}
+
+#[derive(Deserialize)]
+struct MetInstant {
+    details: MetDetails,
+}
+
+#[derive(Deserialize)]
+struct MetDetails {
+    air_temperature: f32,
+    relative_humidity: f32,
+    wind_speed: Option<f32>,
+    wind_from_direction: Option<f32>,
+    /// Only in the `complete` variant
+    ultraviolet_index_clear_sky: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct MetPeriod<'a> {
+    #[serde(borrow)]
+    summary: MetSummary<'a>,
+    details: Option<MetPeriodDetails>,
+}
+
+#[derive(Deserialize)]
+struct MetPeriodDetails {
+    precipitation_amount: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct MetSummary<'a> {
+    symbol_code: &'a str,
+}
+
+impl WeatherProvider for MetNorway<'_> {
+    fn url(&self) -> &str {
+        self.url
+    }
+
+    // The full forecast is tens of KiB, the first time step is all we use
+    fn partial_body(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<WeatherReport, HttpError> {
+        let entry = first_element(body, b"timeseries")?;
+        let entry = parse_json::<MetEntry>(entry)?;
+        let data = entry.data;
+        let details = data.instant.details;
+
+        let precipitation = data
+            .next_1_hours
+            .as_ref()
+            .and_then(|p| p.details.as_ref())
+            .and_then(|d| d.precipitation_amount);
+
+        let symbol = data
+            .next_1_hours
+            .or(data.next_6_hours)
+            .map(|p| p.summary.symbol_code);
+
+        // Symbols for the sun's position end in `_day`, `_night` or
+        // `_polartwilight`, the rest look the same at any time
+        let is_night = symbol.is_some_and(|s| s.ends_with("_night"));
+
+        Ok(WeatherReport {
+            current: CurrentWeather {
+                temperature: self.units.from_celsius(details.air_temperature),
+                weathercode: symbol.map_or(UNKNOWN_CODE, met_symbol_to_wmo),
+                relative_humidity_2m: details.relative_humidity,
+                is_day: u8::from(!is_night),
+                apparent_temperature: None,
+                wind_speed_10m: details
+                    .wind_speed
+                    .map(|s| self.units.from_metres_per_second(s)),
+                wind_direction_10m: details.wind_from_direction,
+                precipitation: precipitation.map(|p| self.units.from_millimetres(p)),
+                uv_index: details.ultraviolet_index_clear_sky,
+            },
+            forecast: None,
+            hourly: None,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct GenericReading {
+    temperature: f32,
+    humidity: f32,
+    code: u8,
+    is_day: Option<u8>,
+}
+
+impl WeatherProvider for GenericJson<'_> {
+    fn url(&self) -> &str {
+        self.url
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<WeatherReport, HttpError> {
+        let reading = parse_json::<GenericReading>(body)?;
+
+        Ok(WeatherReport {
+            current: CurrentWeather {
+                temperature: self.units.from_celsius(reading.temperature),
+                weathercode: reading.code,
+                relative_humidity_2m: reading.humidity,
+                is_day: reading.is_day.unwrap_or(1),
+                ..Default::default()
+            },
+            forecast: None,
+            hourly: None,
+        })
+    }
+}
+
+/// Maps a MET Norway symbol such as `lightrainshowers_day` to a WMO code
+pub fn met_symbol_to_wmo(symbol: &str) -> u8 {
+    let symbol = symbol.split('_').next().unwrap_or_default();
+
+    if symbol.contains("thunder") {
+        return 95;
+    }
+
+    match symbol {
+        "clearsky" => 0,
+        "fair" => 1,
+        "partlycloudy" => 2,
+        "cloudy" => 3,
+        "fog" => 45,
+        "lightrain" => 61,
+        "rain" => 63,
+        "heavyrain" => 65,
+        "lightsleet" | "lightsleetshowers" => 66,
+        "sleet" | "heavysleet" | "sleetshowers" | "heavysleetshowers" => 67,
+        "lightsnow" => 71,
+        "snow" => 73,
+        "heavysnow" => 75,
+        "lightrainshowers" => 80,
+        "rainshowers" => 81,
+        "heavyrainshowers" => 82,
+        "lightsnowshowers" | "snowshowers" => 85,
+        "heavysnowshowers" => 86,
+        _ => UNKNOWN_CODE,
+    }
+}
+
+/// Finds the first object in the array under `"key"` without parsing the
+/// rest of the document, which may have been cut off
+fn first_element<'a>(body: &'a [u8], key: &[u8]) -> Result<&'a [u8], HttpError> {
+    let at = body
+        .windows(key.len() + 2)
+        .position(|w| w[0] == b'"' && &w[1..=key.len()] == key && w[key.len() + 1] == b'"')
+        .ok_or(HttpError::Parse)?;
+
+    // Skip to the opening brace of the first element
+    let mut pos = at + key.len() + 2;
+    for expected in [b':', b'[', b'{'] {
+        pos += body[pos..]
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .ok_or(HttpError::Truncated)?;
+
+        if body[pos] != expected {
+            return Err(HttpError::Parse);
+        }
+        pos += 1;
+    }
+
+    let start = pos - 1;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, b) in body[start..].iter().enumerate() {
+        match (in_string, escaped, b) {
+            (true, true, _) => escaped = false,
+            (true, false, b'\\') => escaped = true,
+            (true, false, b'"') => in_string = false,
+            (true, false, _) => {}
+            (false, _, b'"') => in_string = true,
+            (false, _, b'{' | b'[') => depth += 1,
+            (false, _, b'}' | b']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(&body[start..=start + i]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(HttpError::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MET: &[u8] = br#"{"type":"Feature","properties":{"meta":{"units":{"air_temperature":"celsius"}},"timeseries":[{"time":"2026-01-02T03:00:00Z","data":{"instant":{"details":{"air_temperature":-3.4,"relative_humidity":81.2,"wind_speed":4.1,"wind_from_direction":250.3}},"next_1_hours":{"summary":{"symbol_code":"lightsnowshowers_night"},"details":{"precipitation_amount":0.3}},"next_6_hours":{"summary":{"symbol_code":"cloudy"}}}},{"time":"2026-01-02T04:00:00Z","data":{"instant":{"details":{"air_temperature":-3.9"#;
+
+    #[test]
+    fn selects_by_config() {
+        assert!(matches!(
+            Provider::from_config("open-meteo", "u", Units::Metric),
+            Some(Provider::OpenMeteo(_))
+        ));
+        assert!(matches!(
+            Provider::from_config("met-no", "u", Units::Metric),
+            Some(Provider::MetNorway(_))
+        ));
+        assert!(matches!(
+            Provider::from_config("json", "u", Units::Metric),
+            Some(Provider::GenericJson(_))
+        ));
+        assert!(Provider::from_config("darksky", "u", Units::Metric).is_none());
+    }
+
+    #[test]
+    fn open_meteo() {
+        let provider = Provider::from_config("open-meteo", "u", Units::Metric).unwrap();
+        let body = br#"{"current":{"temperature":27.4,"weathercode":2,"relative_humidity_2m":74,"is_day":0}}"#;
+
+        assert_eq!(
+            provider.parse(body),
+            Ok(WeatherReport {
+                current: CurrentWeather {
+                    temperature: 27.4,
+                    weathercode: 2,
+                    relative_humidity_2m: 74.0,
+                    is_day: 0,
+                    ..Default::default()
+                },
+                forecast: None,
+                hourly: None,
+            })
+        );
+        assert!(!provider.partial_body());
+    }
+
+    #[test]
+    fn met_norway_from_truncated_body() {
+        let provider = Provider::from_config("met-no", "u", Units::Metric).unwrap();
+
+        assert!(provider.partial_body());
+        assert_eq!(
+            provider.parse(MET).map(|r| r.current),
+            Ok(CurrentWeather {
+                temperature: -3.4,
+                weathercode: 85,
+                relative_humidity_2m: 81.2,
+                is_day: 0,
+                apparent_temperature: None,
+                wind_speed_10m: Some(4.1 * 3.6),
+                wind_direction_10m: Some(250.3),
+                precipitation: Some(0.3),
+                uv_index: None,
+            })
+        );
+        assert_eq!(provider.parse(&MET[..120]), Err(HttpError::Truncated));
+        assert_eq!(provider.parse(b"{\"type\":1}"), Err(HttpError::Parse));
+
+        let imperial = Provider::from_config("met-no", "u", Units::Imperial).unwrap();
+        let temperature = imperial.parse(MET).unwrap().current.temperature;
+        assert!((temperature - 25.88).abs() < 0.01);
+    }
+
+    #[test]
+    fn met_symbols() {
+        assert_eq!(met_symbol_to_wmo("clearsky_day"), 0);
+        assert_eq!(met_symbol_to_wmo("partlycloudy_polartwilight"), 2);
+        assert_eq!(met_symbol_to_wmo("heavyrainandthunder"), 95);
+        assert_eq!(met_symbol_to_wmo("rain"), 63);
+        assert_eq!(met_symbol_to_wmo("volcano"), UNKNOWN_CODE);
+    }
+
+    #[test]
+    fn generic_json() {
+        let provider =
+            Provider::from_config("json", "http://pi.local/weather", Units::Metric).unwrap();
+
+        assert_eq!(provider.url(), "http://pi.local/weather");
+        assert_eq!(
+            provider
+                .parse(br#"{"temperature":21.5,"humidity":60,"code":3,"station":"attic"}"#)
+                .map(|r| r.current),
+            Ok(CurrentWeather {
+                temperature: 21.5,
+                weathercode: 3,
+                relative_humidity_2m: 60.0,
+                is_day: 1,
+                ..Default::default()
+            })
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! Setting the badge up from a phone. Holding A and B on wake starts an open
+//! access point; joining it and opening [`SETUP_URL`] shows a form for a
+//! WiFi network and the weather location. This module parses the requests
+//! and builds the pages, the firmware moves the bytes.
+
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+
+use crate::{
+    api::Location,
+    storage::{PASSWORD_LEN, StoredNetwork},
+    wifi::SSID_LEN,
+};
+
+/// Name of the access point the badge starts for setup
+pub const AP_SSID: &str = "Badger setup";
+
+/// The badge's address on its own access point, a /24
+pub const AP_ADDRESS: [u8; 4] = [192, 168, 4, 1];
+
+/// What to open once joined, shown on the e-ink
+pub const SETUP_URL: &str = "http://192.168.4.1/";
+
+pub const HTTP_PORT: u16 = 80;
+
+/// Largest request we read, headers included
+pub const REQUEST_SIZE: usize = 1024;
+
+/// Longest latitude or longitude worth parsing
+const COORDINATE_LEN: usize = 16;
+
+/// WPA2 passphrases are 8 to 63 characters, 64 is a raw key
+const MIN_PASSWORD_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Request<'a> {
+    /// `GET /`, the empty form
+    Form,
+    /// `POST /` with the urlencoded form as the body
+    Submit(&'a [u8]),
+    /// Anything else, e.g. the browser asking for a favicon
+    NotFound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RequestError {
+    /// The headers or body haven't all arrived yet, read some more
+    Incomplete,
+    /// Not HTTP we understand
+    Malformed,
+}
+
+/// Parses a request from the start of `buf`, which holds everything read
+/// from the connection so far
+pub fn parse_request(buf: &[u8]) -> Result<Request<'_>, RequestError> {
+    let Some(header_end) = find(buf, b"\r\n\r\n") else {
+        return if buf.len() < REQUEST_SIZE {
+            Err(RequestError::Incomplete)
+        } else {
+            Err(RequestError::Malformed)
+        };
+    };
+
+    let head = core::str::from_utf8(&buf[..header_end]).map_err(|_| RequestError::Malformed)?;
+    let mut lines = head.split("\r\n");
+
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().ok_or(RequestError::Malformed)?;
+
+    if !request_line.next().is_some_and(|v| v.starts_with("HTTP/")) {
+        return Err(RequestError::Malformed);
+    }
+
+    // The form has no other fields worth a query string
+    let path = path.split('?').next().unwrap_or_default();
+
+    match (method, path) {
+        ("GET", "/") => Ok(Request::Form),
+        ("POST", "/") => {
+            let length = lines
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+                .map(|(_, value)| value.trim().parse::<usize>())
+                .ok_or(RequestError::Malformed)?
+                .map_err(|_| RequestError::Malformed)?;
+
+            let body = &buf[header_end + 4..];
+            // The length comes from the client, so it can be anything
+            let end = header_end
+                .checked_add(4)
+                .and_then(|start| start.checked_add(length))
+                .ok_or(RequestError::Malformed)?;
+
+            match body.get(..length) {
+                Some(body) => Ok(Request::Submit(body)),
+                None if end <= REQUEST_SIZE => Err(RequestError::Incomplete),
+                None => Err(RequestError::Malformed),
+            }
+        }
+        _ => Ok(Request::NotFound),
+    }
+}
+
+/// What the form sets up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setup {
+    pub network: StoredNetwork,
+    /// Left as it was when both coordinates are blank
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FormError {
+    MissingSsid,
+    SsidTooLong,
+    /// Neither blank for an open network nor a valid WPA2 passphrase
+    BadPassword,
+    /// Only one coordinate, or one that isn't decimal degrees
+    BadLocation,
+    /// Not urlencoded, or not UTF-8 where it has to be
+    Malformed,
+}
+
+impl FormError {
+    /// Shown above the form when it comes back
+    pub fn message(&self) -> &'static str {
+        match self {
+            FormError::MissingSsid => "Enter the network name",
+            FormError::SsidTooLong => "Network names are at most 32 bytes",
+            FormError::BadPassword => "Passwords are 8 to 64 characters, or blank",
+            FormError::BadLocation => "Enter both coordinates in decimal degrees, or neither",
+            FormError::Malformed => "The form didn't come through, try again",
+        }
+    }
+}
+
+/// Parses the `application/x-www-form-urlencoded` body of the form
+pub fn parse_form(body: &[u8]) -> Result<Setup, FormError> {
+    let mut ssid = None;
+    let mut password = None;
+    let mut latitude = None;
+    let mut longitude = None;
+
+    for field in body.split(|b| *b == b'&').filter(|f| !f.is_empty()) {
+        let (name, value) = match field.iter().position(|b| *b == b'=') {
+            Some(i) => (&field[..i], &field[i + 1..]),
+            None => (field, &[][..]),
+        };
+
+        match name {
+            b"ssid" => ssid = Some(decode::<SSID_LEN>(value, FormError::SsidTooLong)?),
+            b"password" => password = Some(decode::<PASSWORD_LEN>(value, FormError::BadPassword)?),
+            b"latitude" => {
+                latitude = Some(decode::<COORDINATE_LEN>(value, FormError::BadLocation)?)
+            }
+            b"longitude" => {
+                longitude = Some(decode::<COORDINATE_LEN>(value, FormError::BadLocation)?)
+            }
+            _ => {}
+        }
+    }
+
+    let ssid = ssid.ok_or(FormError::MissingSsid)?;
+    let ssid = core::str::from_utf8(&ssid).map_err(|_| FormError::Malformed)?;
+    let ssid = String::try_from(ssid).map_err(|_| FormError::SsidTooLong)?;
+    if ssid.trim().is_empty() {
+        return Err(FormError::MissingSsid);
+    }
+
+    let password = password.unwrap_or_default();
+    if !password.is_empty() && password.len() < MIN_PASSWORD_LEN {
+        return Err(FormError::BadPassword);
+    }
+
+    let location = match (coordinate(&latitude)?, coordinate(&longitude)?) {
+        ("", "") => None,
+        (latitude, longitude) => {
+            Some(Location::parse(latitude, longitude).ok_or(FormError::BadLocation)?)
+        }
+    };
+
+    Ok(Setup {
+        network: StoredNetwork {
+            ssid,
+            password,
+            ipv4: None,
+        },
+        location,
+    })
+}
+
+/// A trimmed coordinate field, empty if left out
+fn coordinate(value: &Option<Vec<u8, COORDINATE_LEN>>) -> Result<&str, FormError> {
+    core::str::from_utf8(value.as_deref().unwrap_or_default())
+        .map(str::trim)
+        .map_err(|_| FormError::BadLocation)
+}
+
+/// Undoes urlencoding: `+` is a space and `%XX` a byte. Fails with
+/// `too_long` if it doesn't fit in `N` bytes.
+fn decode<const N: usize>(value: &[u8], too_long: FormError) -> Result<Vec<u8, N>, FormError> {
+    let mut decoded = Vec::new();
+    let mut bytes = value.iter();
+
+    while let Some(byte) = bytes.next() {
+        let byte = match byte {
+            b'+' => b' ',
+            b'%' => {
+                let mut digit = || {
+                    bytes
+                        .next()
+                        .and_then(|d| (*d as char).to_digit(16))
+                        .ok_or(FormError::Malformed)
+                };
+                (digit()? << 4 | digit()?) as u8
+            }
+            byte => *byte,
+        };
+
+        decoded.push(byte).map_err(|_| too_long)?;
+    }
+
+    Ok(decoded)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Status {
+    Ok,
+    BadRequest,
+    NotFound,
+}
+
+impl Status {
+    fn line(&self) -> &'static str {
+        match self {
+            Status::Ok => "200 OK",
+            Status::BadRequest => "400 Bad Request",
+            Status::NotFound => "404 Not Found",
+        }
+    }
+}
+
+/// Room for the form with the longest error message
+pub const PAGE_LEN: usize = 1536;
+
+/// Status line and headers for a page of `content_length` bytes. Every
+/// connection is closed after one response.
+pub fn response_head(status: Status, content_length: usize) -> String<128> {
+    let mut head = String::new();
+    let _ = write!(
+        head,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status.line(),
+        content_length
+    );
+    head
+}
+
+const PAGE_START: &str = "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+<meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">\
+<title>Badger setup</title>\
+<style>body{font-family:sans-serif;max-width:24em;margin:auto;padding:1em}\
+label,input,button{display:block;width:100%;margin:.3em 0}\
+.error{color:#b00}</style></head><body><h1>Badger setup</h1>";
+
+const PAGE_END: &str = "</body></html>";
+
+const FORM: &str = "<form method=\"post\" action=\"/\">\
+<label>WiFi network<input name=\"ssid\" maxlength=\"32\" required></label>\
+<label>Password, blank if open<input name=\"password\" type=\"password\" maxlength=\"64\"></label>\
+<p>Weather location in decimal degrees, blank to keep the current one</p>\
+<label>Latitude<input name=\"latitude\" inputmode=\"decimal\" placeholder=\"51.5072\"></label>\
+<label>Longitude<input name=\"longitude\" inputmode=\"decimal\" placeholder=\"-0.1276\"></label>\
+<button>Save</button></form>";
+
+/// The setup form, with what was wrong with the last one if it came back
+pub fn form_page(error: Option<FormError>) -> String<PAGE_LEN> {
+    let mut page = String::new();
+    let _ = page.push_str(PAGE_START);
+
+    if let Some(error) = error {
+        let _ = write!(page, "<p class=\"error\">{}</p>", error.message());
+    }
+
+    let _ = page.push_str(FORM);
+    let _ = page.push_str(PAGE_END);
+    page
+}
+
+/// Sent once the form is saved, just before the access point goes away
+pub fn saved_page() -> String<PAGE_LEN> {
+    let mut page = String::new();
+    let _ = page.push_str(PAGE_START);
+    let _ = page.push_str("<p>Saved. The badge is leaving setup and will try the network now.</p>");
+    let _ = page.push_str(PAGE_END);
+    page
+}
+
+pub fn not_found_page() -> String<PAGE_LEN> {
+    let mut page = String::new();
+    let _ = page.push_str(PAGE_START);
+    let _ = page.push_str("<p>Nothing here, <a href=\"/\">go to setup</a>.</p>");
+    let _ = page.push_str(PAGE_END);
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(body: &str) -> std::string::String {
+        format!(
+            "POST / HTTP/1.1\r\nHost: 192.168.4.1\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn routes_requests() {
+        assert_eq!(
+            parse_request(b"GET / HTTP/1.1\r\nHost: 192.168.4.1\r\n\r\n"),
+            Ok(Request::Form)
+        );
+        assert_eq!(
+            parse_request(b"GET /favicon.ico HTTP/1.1\r\n\r\n"),
+            Ok(Request::NotFound)
+        );
+        assert_eq!(
+            parse_request(post("ssid=Home").as_bytes()),
+            Ok(Request::Submit(b"ssid=Home"))
+        );
+        assert_eq!(
+            parse_request(b"nonsense\r\n\r\n"),
+            Err(RequestError::Malformed)
+        );
+    }
+
+    #[test]
+    fn waits_for_the_whole_request() {
+        let request = post("ssid=Home&password=hunter22");
+
+        assert_eq!(
+            parse_request(b"GET / HTTP/1.1\r\n"),
+            Err(RequestError::Incomplete)
+        );
+        assert_eq!(
+            parse_request(&request.as_bytes()[..request.len() - 3]),
+            Err(RequestError::Incomplete)
+        );
+        assert_eq!(
+            parse_request(b"POST / HTTP/1.1\r\n\r\nssid=Home"),
+            Err(RequestError::Malformed)
+        );
+        assert_eq!(
+            parse_request(&[b'x'; REQUEST_SIZE]),
+            Err(RequestError::Malformed)
+        );
+    }
+
+    #[test]
+    fn refuses_huge_content_length() {
+        for length in [usize::MAX, usize::MAX - 3, REQUEST_SIZE] {
+            let request = format!("POST / HTTP/1.1\r\nContent-Length: {length}\r\n\r\nssid=Home");
+
+            assert_eq!(
+                parse_request(request.as_bytes()),
+                Err(RequestError::Malformed)
+            );
+        }
+    }
+
+    #[test]
+    fn parses_the_form() {
+        let setup = parse_form(
+            b"ssid=Caf%C3%A9+Wi-Fi&password=p%40ss+word&latitude=51.5072&longitude=-0.1276",
+        )
+        .unwrap();
+
+        assert_eq!(setup.network.ssid, "Café Wi-Fi");
+        assert_eq!(setup.network.password, b"p@ss word");
+        assert_eq!(
+            setup.location,
+            Some(Location {
+                latitude: 51.5072,
+                longitude: -0.1276
+            })
+        );
+
+        let open = parse_form(b"ssid=Phone&password=&latitude=&longitude=").unwrap();
+        assert!(open.network.password.is_empty());
+        assert_eq!(open.location, None);
+    }
+
+    #[test]
+    fn rejects_bad_forms() {
+        assert_eq!(
+            parse_form(b"password=hunter22"),
+            Err(FormError::MissingSsid)
+        );
+        assert_eq!(parse_form(b"ssid=+"), Err(FormError::MissingSsid));
+        assert_eq!(
+            parse_form(&[&b"ssid="[..], &[b'a'; 33]].concat()),
+            Err(FormError::SsidTooLong)
+        );
+        assert_eq!(
+            parse_form(b"ssid=Home&password=short"),
+            Err(FormError::BadPassword)
+        );
+        assert_eq!(
+            parse_form(b"ssid=Home&latitude=51.5"),
+            Err(FormError::BadLocation)
+        );
+        assert_eq!(
+            parse_form(b"ssid=Home&latitude=91&longitude=0"),
+            Err(FormError::BadLocation)
+        );
+        assert_eq!(parse_form(b"ssid=%ZZ"), Err(FormError::Malformed));
+        assert_eq!(parse_form(b"ssid=Home%2"), Err(FormError::Malformed));
+        assert_eq!(parse_form(b"ssid=%FF"), Err(FormError::Malformed));
+    }
+
+    #[test]
+    fn pages_fit() {
+        let longest = [
+            FormError::MissingSsid,
+            FormError::SsidTooLong,
+            FormError::BadPassword,
+            FormError::BadLocation,
+            FormError::Malformed,
+        ]
+        .map(|error| form_page(Some(error)).len())
+        .into_iter()
+        .max()
+        .unwrap();
+
+        assert!(longest < PAGE_LEN);
+        assert!(form_page(None).ends_with(PAGE_END));
+        assert!(
+            response_head(Status::Ok, longest)
+                .ends_with(&format!("Length: {longest}\r\nConnection: close\r\n\r\n"))
+        );
+    }
+}
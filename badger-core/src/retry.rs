@@ -0,0 +1,140 @@
+//! Retry policy for API fetches: exponential backoff with jitter, bounded by
+//! what is left of the sync's time budget.
+
+use crate::http::HttpError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Attempts including the first one
+    pub max_attempts: u8,
+    /// Delay before the first retry, doubled for each one after
+    pub initial_ms: u32,
+    pub max_delay_ms: u32,
+    /// Budget a retry needs after its delay to be worth starting
+    pub min_attempt_ms: u32,
+}
+
+/// Tracks the attempts of one request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retry {
+    backoff: Backoff,
+    attempts: u8,
+}
+
+impl Retry {
+    pub const fn new(backoff: Backoff) -> Self {
+        Retry {
+            backoff,
+            attempts: 0,
+        }
+    }
+
+    /// Call before each attempt
+    pub fn start_attempt(&mut self) {
+        self.attempts = self.attempts.saturating_add(1);
+    }
+
+    /// Attempts made so far
+    pub fn attempts(&self) -> u8 {
+        self.attempts
+    }
+
+    /// After a failed attempt, how long to wait before the next one, or
+    /// `None` to give up. `random` jitters the delay between half and all of
+    /// the backoff, `remaining_ms` is what's left of the sync budget.
+    pub fn next_delay(&self, error: &HttpError, random: u32, remaining_ms: u64) -> Option<u32> {
+        if !is_retryable(error) || self.attempts >= self.backoff.max_attempts {
+            return None;
+        }
+
+        let shift = self.attempts.saturating_sub(1).min(31);
+        let backoff = self
+            .backoff
+            .initial_ms
+            .saturating_mul(1 << shift)
+            .min(self.backoff.max_delay_ms);
+
+        let half = backoff / 2;
+        let delay = half + random % (backoff - half + 1);
+
+        let needed = u64::from(delay) + u64::from(self.backoff.min_attempt_ms);
+        (needed <= remaining_ms).then_some(delay)
+    }
+}
+
+/// Failures that may go away on their own. A bad URL, an unexpected body or
+/// a 4xx won't get better by asking again.
+pub fn is_retryable(error: &HttpError) -> bool {
+    match error {
+        HttpError::Dns
+        | HttpError::Connect
+        | HttpError::Timeout
+        | HttpError::Malformed
+        | HttpError::Truncated => true,
+        HttpError::Status(code) => matches!(code, 408 | 429 | 500..=599),
+        HttpError::InvalidUrl
+        | HttpError::Tls
+        | HttpError::BadRedirect
+        | HttpError::TooManyRedirects
+        | HttpError::ContentType
+        | HttpError::BufferTooSmall
+        | HttpError::Parse => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKOFF: Backoff = Backoff {
+        max_attempts: 4,
+        initial_ms: 1000,
+        max_delay_ms: 3000,
+        min_attempt_ms: 2000,
+    };
+
+    fn after(attempts: u8) -> Retry {
+        let mut retry = Retry::new(BACKOFF);
+        for _ in 0..attempts {
+            retry.start_attempt();
+        }
+        retry
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let error = HttpError::Connect;
+
+        assert_eq!(after(1).next_delay(&error, 0, 60_000), Some(500));
+        assert_eq!(after(1).next_delay(&error, 500, 60_000), Some(1000));
+        assert_eq!(after(2).next_delay(&error, 0, 60_000), Some(1000));
+        assert!(after(2).next_delay(&error, u32::MAX, 60_000).unwrap() <= 2000);
+        // Capped at max_delay_ms
+        assert_eq!(after(3).next_delay(&error, 1500, 60_000), Some(3000));
+    }
+
+    #[test]
+    fn stops_after_max_attempts() {
+        assert!(after(3).next_delay(&HttpError::Dns, 0, 60_000).is_some());
+        assert_eq!(after(4).next_delay(&HttpError::Dns, 0, 60_000), None);
+        assert_eq!(after(4).attempts(), 4);
+    }
+
+    #[test]
+    fn stays_within_budget() {
+        assert_eq!(after(1).next_delay(&HttpError::Timeout, 0, 2500), Some(500));
+        assert_eq!(after(1).next_delay(&HttpError::Timeout, 0, 2499), None);
+    }
+
+    #[test]
+    fn only_retries_transient_errors() {
+        assert!(is_retryable(&HttpError::Status(429)));
+        assert!(is_retryable(&HttpError::Status(503)));
+        assert!(!is_retryable(&HttpError::Status(404)));
+        assert!(!is_retryable(&HttpError::Parse));
+        assert_eq!(
+            after(1).next_delay(&HttpError::ContentType, 0, 60_000),
+            None
+        );
+    }
+}
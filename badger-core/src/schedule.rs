@@ -0,0 +1,101 @@
+use time::{Duration, PrimitiveDateTime, UtcOffset};
+
+/// Minutes between the first catch-up sync and the hourly one it replaces,
+/// doubled for each one after
+const CATCH_UP_FIRST_DELAY: i64 = 5;
+const CATCH_UP_MAX_DELAY: i64 = 60;
+
+/// RTC alarms on the hour also sync time and weather over WiFi
+pub fn is_sync_time(now: PrimitiveDateTime) -> bool {
+    now.minute() == 0
+}
+
+/// Whether a minute wake should retry a missed sync, given the time since the
+/// last one that succeeded. The hourly sync is due an hour after it; when
+/// that one fails, retries follow 5, 10, 20 and 40 minutes apart, then every
+/// hour, until one succeeds and moves the starting point.
+pub fn is_catch_up_time(since_sync: Duration) -> bool {
+    let minutes = since_sync.whole_minutes();
+
+    let mut delay = CATCH_UP_FIRST_DELAY;
+    let mut due = 60 + delay;
+
+    while due < minutes {
+        delay = (delay * 2).min(CATCH_UP_MAX_DELAY);
+        due += delay;
+    }
+
+    due == minutes
+}
+
+/// Minute of the UTC hour that is the top of the local hour, for arming the
+/// hourly RTC alarm in zones offset by a fraction of an hour
+pub fn hourly_alarm_minute(offset: UtcOffset) -> u8 {
+    let past_hour = offset.minutes_past_hour().rem_euclid(60) as u8;
+
+    (60 - past_hour) % 60
+}
+
+/// How long to sleep before reading the RTC again so the clock ticks over
+/// close to the top of the minute
+pub fn secs_until_next_minute(now: Option<PrimitiveDateTime>) -> u64 {
+    match now {
+        Some(time) => 60 - time.second().clamp(0, 50),
+        None => 60,
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month, Time};
+
+    fn at(hour: u8, minute: u8, second: u8) -> PrimitiveDateTime {
+        PrimitiveDateTime::new(
+            Date::from_calendar_date(2026, Month::January, 1).unwrap(),
+            Time::from_hms(hour, minute, second).unwrap(),
+        )
+    }
+
+    #[test]
+    fn syncs_on_the_hour() {
+        assert!(is_sync_time(at(0, 0, 0)));
+        assert!(is_sync_time(at(13, 0, 30)));
+        assert!(!is_sync_time(at(13, 1, 0)));
+        assert!(!is_sync_time(at(13, 59, 59)));
+    }
+
+    #[test]
+    fn catch_up_backs_off_after_a_missed_sync() {
+        let due: Vec<i64> = (0..400)
+            .filter(|m| is_catch_up_time(Duration::minutes(*m)))
+            .collect();
+
+        assert_eq!(due, [65, 75, 95, 135, 195, 255, 315, 375]);
+
+        // Only whole minutes count, so a wake that comes up to a minute
+        // after the due time, here 40 seconds, still retries
+        assert!(is_catch_up_time(Duration::seconds(65 * 60 + 40)));
+        assert!(!is_catch_up_time(Duration::minutes(-65)));
+    }
+
+    #[test]
+    fn hourly_alarm_follows_local_hour() {
+        let offset = |h, m| UtcOffset::from_hms(h, m, 0).unwrap();
+
+        assert_eq!(hourly_alarm_minute(UtcOffset::UTC), 0);
+        assert_eq!(hourly_alarm_minute(offset(-6, 0)), 0);
+        assert_eq!(hourly_alarm_minute(offset(5, 30)), 30);
+        assert_eq!(hourly_alarm_minute(offset(5, 45)), 15);
+        assert_eq!(hourly_alarm_minute(offset(-3, -30)), 30);
+    }
+
+    #[test]
+    fn clock_delay_never_drops_below_ten_seconds() {
+        assert_eq!(secs_until_next_minute(None), 60);
+        assert_eq!(secs_until_next_minute(Some(at(1, 2, 0))), 60);
+        assert_eq!(secs_until_next_minute(Some(at(1, 2, 45))), 15);
+        assert_eq!(secs_until_next_minute(Some(at(1, 2, 59))), 10);
+    }
+}
@@ -0,0 +1,282 @@
+//! Minimal SNTP (RFC 4330) client logic. The network I/O is supplied by a
+//! [`Transport`], so the same code runs over embassy-net on the badge and over
+//! std sockets in tests.
+
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+pub const NTP_PORT: u16 = 123;
+pub const PACKET_SIZE: usize = 48;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const UNIX_OFFSET: i64 = 2_208_988_800;
+
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const VERSION: u8 = 4;
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SntpError {
+    /// No servers were configured
+    NoServers,
+    /// DNS, socket or timeout failure talking to the server
+    Transport,
+    /// Reply shorter than an NTP header
+    ShortPacket,
+    /// Reply isn't a server-mode packet of a version we understand
+    Malformed,
+    /// Leap indicator says the server clock isn't synchronised
+    Unsynchronized,
+    /// Stratum 0: the server asked us to go away
+    KissOfDeath,
+    /// Stratum above 15
+    BadStratum(u8),
+    /// Reply doesn't echo our transmit timestamp
+    OriginMismatch,
+    /// Request took longer than the configured limit
+    RoundTripTooLong,
+}
+
+/// A way to send one request and wait for one reply
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    /// Sends `request` to `server` (a host name or address) on the NTP port
+    /// and returns the length of the reply written to `response`
+    async fn exchange(
+        &mut self,
+        server: &str,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, SntpError>;
+}
+
+/// Builds a client request. `nonce` is sent as the transmit timestamp and
+/// must come back as the originate timestamp, which is how we match replies
+/// without knowing the time yet.
+pub fn request(nonce: u64) -> [u8; PACKET_SIZE] {
+    let mut packet = [0u8; PACKET_SIZE];
+
+    packet[0] = (VERSION << 3) | MODE_CLIENT;
+    packet[40..48].copy_from_slice(&nonce.to_be_bytes());
+
+    packet
+}
+
+/// Validates a server reply to the request carrying `nonce` and returns the
+/// current UTC time. `elapsed_us` is the locally measured time between
+/// sending the request and receiving the reply.
+pub fn parse_response(
+    response: &[u8],
+    nonce: u64,
+    elapsed_us: u64,
+    max_round_trip_us: u64,
+) -> Result<PrimitiveDateTime, SntpError> {
+    if response.len() < PACKET_SIZE {
+        return Err(SntpError::ShortPacket);
+    }
+
+    let leap = response[0] >> 6;
+    let version = (response[0] >> 3) & 0x7;
+    let mode = response[0] & 0x7;
+    let stratum = response[1];
+
+    if mode != MODE_SERVER || !(1..=4).contains(&version) {
+        return Err(SntpError::Malformed);
+    }
+
+    if leap == LEAP_UNSYNCHRONIZED {
+        return Err(SntpError::Unsynchronized);
+    }
+
+    match stratum {
+        0 => return Err(SntpError::KissOfDeath),
+        1..=15 => {}
+        x => return Err(SntpError::BadStratum(x)),
+    }
+
+    if timestamp(response, 24) != nonce {
+        return Err(SntpError::OriginMismatch);
+    }
+
+    let received = timestamp(response, 32);
+    let transmitted = timestamp(response, 40);
+
+    if transmitted == 0 || transmitted < received {
+        return Err(SntpError::Malformed);
+    }
+
+    let received_us = to_unix_micros(received);
+    let transmitted_us = to_unix_micros(transmitted);
+
+    // Time spent on the wire, excluding the server's processing time
+    let processing_us = (transmitted_us - received_us) as u64;
+    let round_trip_us = elapsed_us.saturating_sub(processing_us);
+
+    if round_trip_us > max_round_trip_us {
+        return Err(SntpError::RoundTripTooLong);
+    }
+
+    let now_us = transmitted_us + (round_trip_us / 2) as i64;
+    let now = OffsetDateTime::from_unix_timestamp_nanos(now_us as i128 * 1000)
+        .map_err(|_| SntpError::Malformed)?;
+
+    Ok(PrimitiveDateTime::new(now.date(), now.time()))
+}
+
+/// Asks each server in turn until one gives a valid answer, returning the
+/// current UTC time or the last server's error
+pub async fn query<T: Transport>(
+    transport: &mut T,
+    servers: &[&str],
+    mut nonce: impl FnMut() -> u64,
+    mut now_us: impl FnMut() -> u64,
+    max_round_trip_us: u64,
+) -> Result<PrimitiveDateTime, SntpError> {
+    let mut result = Err(SntpError::NoServers);
+
+    for server in servers {
+        let nonce = nonce();
+        let mut response = [0u8; PACKET_SIZE];
+
+        let sent = now_us();
+        result = match transport
+            .exchange(server, &request(nonce), &mut response)
+            .await
+        {
+            Ok(len) => parse_response(
+                &response[..len],
+                nonce,
+                now_us().saturating_sub(sent),
+                max_round_trip_us,
+            ),
+            Err(e) => Err(e),
+        };
+
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+fn timestamp(packet: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&packet[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Converts a 64-bit NTP timestamp to microseconds since the Unix epoch.
+/// Seconds below 2^31 are taken to be in era 1, after the 2036 rollover.
+fn to_unix_micros(timestamp: u64) -> i64 {
+    let seconds = timestamp >> 32;
+    let fraction = timestamp & 0xffff_ffff;
+
+    let seconds = if seconds < 0x8000_0000 {
+        seconds + (1 << 32)
+    } else {
+        seconds
+    };
+
+    (seconds as i64 - UNIX_OFFSET) * 1_000_000 + ((fraction * 1_000_000) >> 32) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month, Time};
+
+    const NONCE: u64 = 0x0123_4567_89ab_cdef;
+
+    // 2026-01-02T03:04:05Z in NTP seconds
+    const T: u64 = 1_767_323_045 + UNIX_OFFSET as u64;
+
+    fn reply(leap: u8, stratum: u8, origin: u64, received: u64, transmitted: u64) -> [u8; 48] {
+        let mut packet = [0u8; PACKET_SIZE];
+        packet[0] = (leap << 6) | (VERSION << 3) | MODE_SERVER;
+        packet[1] = stratum;
+        packet[24..32].copy_from_slice(&origin.to_be_bytes());
+        packet[32..40].copy_from_slice(&received.to_be_bytes());
+        packet[40..48].copy_from_slice(&transmitted.to_be_bytes());
+        packet
+    }
+
+    fn good() -> [u8; 48] {
+        reply(0, 2, NONCE, T << 32, T << 32)
+    }
+
+    #[test]
+    fn request_is_client_mode_with_nonce() {
+        let packet = request(NONCE);
+
+        assert_eq!(packet[0], 0x23);
+        assert_eq!(timestamp(&packet, 40), NONCE);
+        assert!(packet[1..40].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn parses_server_time() {
+        let expected = PrimitiveDateTime::new(
+            Date::from_calendar_date(2026, Month::January, 2).unwrap(),
+            Time::from_hms(3, 4, 5).unwrap(),
+        );
+
+        assert_eq!(parse_response(&good(), NONCE, 0, 1_000_000), Ok(expected));
+    }
+
+    #[test]
+    fn adds_half_the_round_trip() {
+        let now = parse_response(&good(), NONCE, 400_000, 1_000_000).unwrap();
+
+        assert_eq!(now.second(), 5);
+        assert_eq!(now.millisecond(), 200);
+    }
+
+    #[test]
+    fn excludes_server_processing_from_round_trip() {
+        // Server held the request for 0.5s, we measured 0.6s
+        let packet = reply(0, 2, NONCE, T << 32, (T << 32) | 0x8000_0000);
+        let now = parse_response(&packet, NONCE, 600_000, 200_000).unwrap();
+
+        assert_eq!(now.millisecond(), 550);
+    }
+
+    #[test]
+    fn rejects_bad_replies() {
+        assert_eq!(
+            parse_response(&good()[..40], NONCE, 0, 1_000_000),
+            Err(SntpError::ShortPacket)
+        );
+        assert_eq!(
+            parse_response(&reply(3, 2, NONCE, T << 32, T << 32), NONCE, 0, 1_000_000),
+            Err(SntpError::Unsynchronized)
+        );
+        assert_eq!(
+            parse_response(&reply(0, 0, NONCE, T << 32, T << 32), NONCE, 0, 1_000_000),
+            Err(SntpError::KissOfDeath)
+        );
+        assert_eq!(
+            parse_response(&reply(0, 16, NONCE, T << 32, T << 32), NONCE, 0, 1_000_000),
+            Err(SntpError::BadStratum(16))
+        );
+        assert_eq!(
+            parse_response(&good(), NONCE + 1, 0, 1_000_000),
+            Err(SntpError::OriginMismatch)
+        );
+        assert_eq!(
+            parse_response(&good(), NONCE, 2_000_000, 1_000_000),
+            Err(SntpError::RoundTripTooLong)
+        );
+        assert_eq!(
+            parse_response(&request(NONCE), NONCE, 0, 1_000_000),
+            Err(SntpError::Malformed)
+        );
+    }
+
+    #[test]
+    fn handles_era_rollover() {
+        // 2036-02-07T06:28:16Z is NTP second 0 of era 1
+        assert_eq!(to_unix_micros(0), 2_085_978_496 * 1_000_000);
+    }
+}
@@ -0,0 +1,445 @@
+use heapless::{String, Vec};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    air::AirQuality,
+    api::Location,
+    units::Units,
+    weather::{CurrentWeather, Forecast, HourlyForecast},
+    wifi::{Ipv4Settings, MAX_NETWORKS, Network, Reconnect, SSID_LEN},
+};
+
+/// Space reserved for a serialized `Postcard`
+pub const POSTCARD_SIZE: usize = 512;
+
+/// Space reserved for serialized `Credentials`
+pub const CREDENTIALS_SIZE: usize = 1024;
+
+/// Written before the `Postcard` record. Bump it when the struct changes,
+/// and a record of any other version is read as empty. The versions start
+/// above any first byte the records had before they were versioned.
+pub const POSTCARD_VERSION: u8 = 0x12;
+
+/// Written before the `Credentials` record, see [`POSTCARD_VERSION`]
+pub const CREDENTIALS_VERSION: u8 = 0x10;
+
+/// Longest WPA passphrase is 63 characters, a raw key 64 hex digits
+pub const PASSWORD_LEN: usize = 64;
+
+/// State persisted to flash between deep sleeps
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct Postcard {
+    pub weather: Option<CurrentWeather>,
+    /// When `weather` was fetched, see [`to_unix_time`]
+    pub weather_fetched: Option<i64>,
+    /// When the last sync succeeded, in the same form
+    pub last_sync: Option<i64>,
+    /// SSID of the network that worked last, to try it first next time
+    pub last_network: Option<String<SSID_LEN>>,
+    /// How to rejoin it without a scan or DHCP
+    pub reconnect: Option<Reconnect>,
+    pub image: usize,
+    pub forecast: Option<Forecast>,
+    pub hourly: Option<HourlyForecast>,
+    pub air_quality: Option<AirQuality>,
+    /// What the weather above was fetched in
+    pub units: Units,
+}
+
+impl Postcard {
+    pub fn to_slice<'a>(&self, buf: &'a mut [u8]) -> Result<&'a mut [u8], postcard::Error> {
+        to_versioned_slice(self, POSTCARD_VERSION, buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, postcard::Error> {
+        from_versioned_bytes(buf, POSTCARD_VERSION)
+    }
+}
+
+/// One network of [`Credentials`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StoredNetwork {
+    pub ssid: String<SSID_LEN>,
+    pub password: Vec<u8, PASSWORD_LEN>,
+    /// A static address instead of DHCP
+    pub ipv4: Option<Ipv4Settings>,
+}
+
+/// WiFi networks, highest priority first, and the location set up with
+/// them. They have a flash record of their own, as they change far less
+/// often than the weather and shouldn't be lost with it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Credentials {
+    pub networks: Vec<StoredNetwork, MAX_NETWORKS>,
+    /// Set during setup, overrides `LATITUDE` and `LONGITUDE`
+    pub location: Option<Location>,
+}
+
+impl Credentials {
+    /// Copies `networks`, leaving out any with a password too long to store
+    pub fn from_networks(networks: &[Network]) -> Self {
+        let networks = networks
+            .iter()
+            .filter_map(|network| {
+                Some(StoredNetwork {
+                    ssid: String::try_from(network.ssid).ok()?,
+                    password: Vec::from_slice(network.password).ok()?,
+                    ipv4: None,
+                })
+            })
+            .take(MAX_NETWORKS)
+            .collect();
+
+        Self {
+            networks,
+            location: None,
+        }
+    }
+
+    /// Makes `network` the highest priority, replacing a network with the
+    /// same SSID or, when full, the lowest priority one. A replaced network's
+    /// static address carries over unless `network` has its own.
+    pub fn add(&mut self, mut network: StoredNetwork) {
+        if let Some(replaced) = self.network(&network.ssid) {
+            network.ipv4 = network.ipv4.or_else(|| replaced.ipv4.clone());
+        }
+
+        self.networks.retain(|stored| stored.ssid != network.ssid);
+        if self.networks.is_full() {
+            self.networks.pop();
+        }
+
+        let _ = self.networks.insert(0, network);
+    }
+
+    pub fn network(&self, ssid: &str) -> Option<&StoredNetwork> {
+        self.networks.iter().find(|network| network.ssid == ssid)
+    }
+
+    /// Gives the network called `ssid` a static address. Returns whether
+    /// that changed anything, or `None` if there's no such network.
+    pub fn set_ipv4(&mut self, ssid: &str, ipv4: Ipv4Settings) -> Option<bool> {
+        let network = self
+            .networks
+            .iter_mut()
+            .find(|network| network.ssid == ssid)?;
+
+        let changed = network.ipv4.as_ref() != Some(&ipv4);
+        network.ipv4 = Some(ipv4);
+
+        Some(changed)
+    }
+
+    /// Borrows the networks in the form [`crate::wifi::Scan`] works with
+    pub fn networks(&self) -> Vec<Network<'_>, MAX_NETWORKS> {
+        self.networks
+            .iter()
+            .map(|network| Network {
+                ssid: &network.ssid,
+                password: &network.password,
+            })
+            .collect()
+    }
+
+    pub fn to_slice<'a>(&self, buf: &'a mut [u8]) -> Result<&'a mut [u8], postcard::Error> {
+        to_versioned_slice(self, CREDENTIALS_VERSION, buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, postcard::Error> {
+        from_versioned_bytes(buf, CREDENTIALS_VERSION)
+    }
+}
+
+/// Seconds since the Unix epoch of a UTC time, the flash form of a time
+pub fn to_unix_time(utc: PrimitiveDateTime) -> i64 {
+    utc.assume_utc().unix_timestamp()
+}
+
+/// Reverse of [`to_unix_time`]
+pub fn from_unix_time(seconds: i64) -> Option<PrimitiveDateTime> {
+    let utc = OffsetDateTime::from_unix_timestamp(seconds).ok()?;
+
+    Some(PrimitiveDateTime::new(utc.date(), utc.time()))
+}
+
+/// Serializes `value` after a `version` byte
+fn to_versioned_slice<'a, T: Serialize>(
+    value: &T,
+    version: u8,
+    buf: &'a mut [u8],
+) -> Result<&'a mut [u8], postcard::Error> {
+    let (first, rest) = buf
+        .split_first_mut()
+        .ok_or(postcard::Error::SerializeBufferFull)?;
+    *first = version;
+
+    let len = postcard::to_slice(value, rest)?.len();
+
+    Ok(&mut buf[..len + 1])
+}
+
+/// Deserializes a record written by [`to_versioned_slice`], failing if it
+/// has another version, as if the flash were empty
+fn from_versioned_bytes<'a, T: Deserialize<'a>>(
+    buf: &'a [u8],
+    version: u8,
+) -> Result<T, postcard::Error> {
+    match buf.split_first() {
+        Some((first, rest)) if *first == version => postcard::from_bytes(rest),
+        _ => Err(postcard::Error::DeserializeBadEncoding),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::{DailyForecast, FORECAST_DAYS, FORECAST_HOURS};
+    use crate::wifi::{AccessPoint, MAX_DNS_SERVERS};
+    use time::{Date, Month, Time};
+
+    #[test]
+    fn round_trips_through_flash_buffer() {
+        let postcard = Postcard {
+            weather: Some(CurrentWeather {
+                temperature: -3.5,
+                weathercode: 73,
+                relative_humidity_2m: 91.0,
+                is_day: 0,
+                apparent_temperature: Some(-9.0),
+                wind_speed_10m: Some(22.5),
+                wind_direction_10m: Some(340.0),
+                precipitation: Some(1.2),
+                uv_index: None,
+            }),
+            weather_fetched: Some(1_767_323_045),
+            last_sync: Some(1_767_323_050),
+            last_network: Some(String::try_from("Phone hotspot").unwrap()),
+            reconnect: Some(Reconnect {
+                ssid: String::try_from("s".repeat(SSID_LEN).as_str()).unwrap(),
+                access_point: Some(AccessPoint {
+                    bssid: [0xfe; 6],
+                    channel: 165,
+                }),
+                lease: Ipv4Settings {
+                    address: [255; 4],
+                    prefix_len: 32,
+                    gateway: Some([255; 4]),
+                    dns_servers: Vec::from_slice(&[[255; 4]; MAX_DNS_SERVERS]).unwrap(),
+                },
+                leased_at: 1_767_323_000,
+            }),
+            image: 2,
+            forecast: Some(
+                (0..FORECAST_DAYS as u8)
+                    .map(|i| DailyForecast {
+                        weekday: i,
+                        high: 30.5,
+                        low: -12.25,
+                        weathercode: 95,
+                        precipitation_probability: Some(100),
+                    })
+                    .collect(),
+            ),
+            hourly: Some(HourlyForecast {
+                start_hour: 23,
+                temperature: (0..FORECAST_HOURS).map(|i| i as f32 - 40.5).collect(),
+                precipitation_probability: (0..FORECAST_HOURS).map(|i| i as u8 * 4).collect(),
+            }),
+            air_quality: Some(AirQuality {
+                us_aqi: Some(163.0),
+                pm2_5: Some(78.4),
+                alder_pollen: None,
+                birch_pollen: Some(1200.0),
+                grass_pollen: Some(0.0),
+                ragweed_pollen: None,
+            }),
+            units: Units::Imperial,
+        };
+
+        let mut buf = [0xffu8; POSTCARD_SIZE];
+        postcard.to_slice(&mut buf).unwrap();
+
+        assert_eq!(Postcard::from_bytes(&buf).unwrap(), postcard);
+    }
+
+    #[test]
+    fn unix_time_round_trips() {
+        let utc = from_unix_time(1_767_323_045).unwrap();
+
+        assert_eq!(
+            utc,
+            PrimitiveDateTime::new(
+                Date::from_calendar_date(2026, Month::January, 2).unwrap(),
+                Time::from_hms(3, 4, 5).unwrap(),
+            )
+        );
+        assert_eq!(to_unix_time(utc), 1_767_323_045);
+        assert_eq!(from_unix_time(i64::MAX), None);
+    }
+
+    #[test]
+    fn erased_flash_is_rejected() {
+        assert!(Postcard::from_bytes(&[0xff; POSTCARD_SIZE]).is_err());
+        assert!(Credentials::from_bytes(&[0xff; CREDENTIALS_SIZE]).is_err());
+    }
+
+    #[test]
+    fn other_versions_read_as_empty() {
+        let postcard = Postcard {
+            image: 2,
+            ..Default::default()
+        };
+        let credentials = Credentials::from_networks(&[Network {
+            ssid: "Home",
+            password: b"secret",
+        }]);
+
+        let mut buf = [0u8; CREDENTIALS_SIZE];
+        let written = postcard.to_slice(&mut buf).unwrap();
+        assert_eq!(written[0], POSTCARD_VERSION);
+        assert_eq!(Postcard::from_bytes(written), Ok(postcard));
+
+        written[0] = POSTCARD_VERSION + 1;
+        assert!(Postcard::from_bytes(written).is_err());
+
+        let written = credentials.to_slice(&mut buf).unwrap();
+        assert_eq!(written[0], CREDENTIALS_VERSION);
+        assert_eq!(Credentials::from_bytes(written), Ok(credentials.clone()));
+
+        written[0] = CREDENTIALS_VERSION - 1;
+        assert!(Credentials::from_bytes(written).is_err());
+
+        // A record from before versioning
+        let unversioned = postcard::to_slice(&credentials, &mut buf).unwrap();
+        assert!(Credentials::from_bytes(unversioned).is_err());
+    }
+
+    #[test]
+    fn credentials_fit_their_record() {
+        let ssid = "s".repeat(SSID_LEN);
+        let password = [b'p'; PASSWORD_LEN];
+        let networks = [Network {
+            ssid: &ssid,
+            password: &password,
+        }; MAX_NETWORKS];
+
+        let mut credentials = Credentials::from_networks(&networks);
+        assert_eq!(credentials.networks().as_slice(), networks);
+
+        for network in &mut credentials.networks {
+            network.ipv4 = Some(Ipv4Settings {
+                address: [255; 4],
+                prefix_len: 32,
+                gateway: Some([255; 4]),
+                dns_servers: Vec::from_slice(&[[255; 4]; MAX_DNS_SERVERS]).unwrap(),
+            });
+        }
+
+        credentials.location = Some(Location {
+            latitude: -33.8688,
+            longitude: 151.2093,
+        });
+
+        let mut buf = [0xffu8; CREDENTIALS_SIZE];
+        credentials.to_slice(&mut buf).unwrap();
+
+        assert_eq!(Credentials::from_bytes(&buf).unwrap(), credentials);
+    }
+
+    #[test]
+    fn credentials_skip_unstorable_passwords() {
+        let long = [b'p'; PASSWORD_LEN + 1];
+        let networks = [
+            Network {
+                ssid: "Home",
+                password: &long,
+            },
+            Network {
+                ssid: "Phone",
+                password: b"",
+            },
+        ];
+
+        assert_eq!(
+            Credentials::from_networks(&networks).networks().as_slice(),
+            &networks[1..]
+        );
+    }
+
+    #[test]
+    fn added_network_goes_first() {
+        let stored = |ssid: &str| StoredNetwork {
+            ssid: String::try_from(ssid).unwrap(),
+            password: Vec::from_slice(b"hunter22").unwrap(),
+            ipv4: None,
+        };
+        let ssids = |credentials: &Credentials| {
+            credentials
+                .networks()
+                .iter()
+                .map(|network| network.ssid.chars().next().unwrap())
+                .collect::<std::string::String>()
+        };
+
+        let mut credentials = Credentials::default();
+        for ssid in ["a", "b", "c"] {
+            credentials.add(stored(ssid));
+        }
+        assert_eq!(ssids(&credentials), "cba");
+
+        credentials.add(stored("a"));
+        assert_eq!(ssids(&credentials), "acb");
+
+        for ssid in ["d", "e", "f", "g", "h", "i"] {
+            credentials.add(stored(ssid));
+        }
+        assert_eq!(ssids(&credentials), "ihgfedac");
+    }
+
+    #[test]
+    fn static_address_survives_setup() {
+        let ipv4 = Ipv4Settings::parse("10.0.0.9/24,10.0.0.1").unwrap();
+        let mut credentials = Credentials::from_networks(&[Network {
+            ssid: "Lab",
+            password: b"old password",
+        }]);
+
+        assert_eq!(credentials.set_ipv4("Lab", ipv4.clone()), Some(true));
+        assert_eq!(credentials.set_ipv4("Cafe", ipv4.clone()), None);
+
+        credentials.add(StoredNetwork {
+            ssid: String::try_from("Lab").unwrap(),
+            password: Vec::from_slice(b"new password").unwrap(),
+            ipv4: None,
+        });
+
+        let lab = credentials.network("Lab").unwrap();
+        assert_eq!(lab.password, b"new password");
+        assert_eq!(lab.ipv4, Some(ipv4));
+        assert_eq!(credentials.networks.len(), 1);
+    }
+
+    #[test]
+    fn static_address_reaches_stored_credentials() {
+        // Set up from a phone before the address was configured
+        let mut buf = [0u8; CREDENTIALS_SIZE];
+        let stored = Credentials::from_networks(&[Network {
+            ssid: "Lab",
+            password: b"password",
+        }]);
+        let stored = stored.to_slice(&mut buf).unwrap();
+        let mut credentials = Credentials::from_bytes(stored).unwrap();
+
+        let ipv4 = Ipv4Settings::parse("10.0.0.9/24,10.0.0.1").unwrap();
+        assert_eq!(credentials.set_ipv4("Lab", ipv4.clone()), Some(true));
+        assert_eq!(credentials.network("Lab").unwrap().ipv4, Some(ipv4.clone()));
+
+        // Every boot applies it again, which only needs saving once
+        assert_eq!(credentials.set_ipv4("Lab", ipv4), Some(false));
+
+        let moved = Ipv4Settings::parse("10.0.0.10/24,10.0.0.1").unwrap();
+        assert_eq!(credentials.set_ipv4("Lab", moved.clone()), Some(true));
+        assert_eq!(credentials.network("Lab").unwrap().ipv4, Some(moved));
+    }
+}
@@ -0,0 +1,114 @@
+use heapless::String;
+
+use crate::{helpers::easy_format, http::HttpError, sntp::SntpError};
+
+/// Why the last WiFi sync didn't fully succeed
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SyncError {
+    /// Couldn't join the WiFi network
+    Join,
+    /// The sync ran past its time budget
+    Timeout,
+    Time(SntpError),
+    Weather(HttpError),
+    /// The weather came through but the air quality didn't
+    AirQuality(HttpError),
+}
+
+/// What the last sync took, for the logs and for tuning the retries and
+/// the join
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SyncStats {
+    /// Requests made for the weather, 1 when the first one worked
+    pub weather_attempts: u8,
+    /// Same for the air quality, 0 when it isn't fetched
+    pub air_quality_attempts: u8,
+    /// Joined by reusing the last network's lease, without a scan or DHCP
+    pub fast_reconnect: bool,
+    /// From the start of the sync until a network accepted us, scan
+    /// included
+    pub join_ms: u32,
+    /// From then until the network config was up, i.e. DHCP
+    pub network_ms: u32,
+    /// The whole sync, fetches included
+    pub sync_ms: u32,
+}
+
+impl SyncError {
+    /// The time and weather came through, only the extras failed, so the
+    /// sync doesn't need retrying
+    pub fn is_partial(&self) -> bool {
+        matches!(self, SyncError::AirQuality(_))
+    }
+
+    /// The weather request never got an answer. After a fast reconnect that
+    /// is what an address the network no longer routes looks like, so it's
+    /// worth asking DHCP for a new one.
+    pub fn is_network(&self) -> bool {
+        matches!(
+            self,
+            SyncError::Weather(HttpError::Dns | HttpError::Connect | HttpError::Timeout)
+        )
+    }
+
+    /// Short text for the top bar
+    pub fn label(&self) -> String<16> {
+        let text = match self {
+            SyncError::Join => "No WiFi",
+            SyncError::Timeout => "Sync timeout",
+            SyncError::Time(_) => "NTP failed",
+            SyncError::Weather(HttpError::Status(code)) => {
+                return easy_format::<16>(format_args!("HTTP {code}"));
+            }
+            SyncError::Weather(HttpError::Dns) => "DNS failed",
+            SyncError::Weather(HttpError::Connect) => "No connection",
+            SyncError::Weather(HttpError::Timeout) => "HTTP timeout",
+            SyncError::Weather(HttpError::InvalidUrl) => "Bad URL",
+            SyncError::Weather(HttpError::Tls) => "TLS failed",
+            SyncError::Weather(HttpError::Malformed) => "Bad HTTP reply",
+            SyncError::Weather(HttpError::BadRedirect) => "Bad redirect",
+            SyncError::Weather(HttpError::TooManyRedirects) => "Redirect loop",
+            SyncError::Weather(HttpError::ContentType) => "Not JSON",
+            SyncError::Weather(HttpError::BufferTooSmall) => "Reply too big",
+            SyncError::Weather(HttpError::Truncated) => "Reply cut off",
+            SyncError::Weather(HttpError::Parse) => "Bad weather data",
+            SyncError::AirQuality(_) => "No air quality",
+        };
+
+        easy_format::<16>(format_args!("{text}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_fit_and_tell_causes_apart() {
+        assert_eq!(SyncError::Join.label(), "No WiFi");
+        assert_eq!(
+            SyncError::Weather(HttpError::Status(429)).label(),
+            "HTTP 429"
+        );
+        assert_eq!(SyncError::Time(SntpError::Transport).label(), "NTP failed");
+    }
+
+    #[test]
+    fn only_missing_extras_are_partial() {
+        assert!(SyncError::AirQuality(HttpError::Timeout).is_partial());
+        assert!(!SyncError::Weather(HttpError::Timeout).is_partial());
+        assert!(!SyncError::Time(SntpError::Transport).is_partial());
+    }
+
+    #[test]
+    fn only_unanswered_weather_requests_are_network_errors() {
+        assert!(SyncError::Weather(HttpError::Dns).is_network());
+        assert!(SyncError::Weather(HttpError::Connect).is_network());
+        assert!(SyncError::Weather(HttpError::Timeout).is_network());
+        assert!(!SyncError::Weather(HttpError::Status(503)).is_network());
+        assert!(!SyncError::AirQuality(HttpError::Connect).is_network());
+        assert!(!SyncError::Join.is_network());
+    }
+}
@@ -0,0 +1,357 @@
+//! Trust settings for HTTPS requests, and the verifier that checks a
+//! pinned key.
+//!
+//! The TLS stack (embedded-tls) speaks TLS 1.3 with
+//! `TLS_AES_128_GCM_SHA256` and doesn't validate certificate chains, so the
+//! server is authenticated with a pre-shared key or a pinned public key.
+//! There is no way to skip authenticating it. A pin is the SHA-256 of the
+//! server certificate's SubjectPublicKeyInfo, so it survives renewing the
+//! certificate with the same key.
+
+use embedded_tls::{
+    Aes128GcmSha256, CertificateEntryRef, CertificateRef, CertificateVerifyRef, CryptoProvider,
+    CryptoRngCore, SignatureScheme, TlsError, TlsVerifier,
+};
+use heapless::Vec;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{DerSignature, Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+pub const MAX_PSK_LEN: usize = 64;
+
+/// SHA-256 of a certificate's DER encoded SubjectPublicKeyInfo
+pub type Pin = [u8; 32];
+
+/// What a TLS 1.3 server signs in its CertificateVerify, for SHA-256
+pub const CERTIFICATE_VERIFY_LEN: usize = 64 + CERTIFICATE_VERIFY_CONTEXT.len() + 32;
+
+const CERTIFICATE_VERIFY_CONTEXT: &[u8] = b"TLS 1.3, server CertificateVerify\0";
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_BIT_STRING: u8 = 0x03;
+const DER_VERSION: u8 = 0xa0;
+
+/// AlgorithmIdentifier contents of an `id-ecPublicKey` on `prime256v1`
+const EC_P256_ALGORITHM: &[u8] = &[
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+    0x03, 0x01, 0x07,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trust<'a> {
+    /// Authenticate both ends with a TLS 1.3 pre-shared key
+    Psk {
+        identity: &'a str,
+        key: Vec<u8, MAX_PSK_LEN>,
+    },
+    /// Accept only a server whose certificate has this public key, which
+    /// must be ECDSA P-256
+    Pin(Pin),
+}
+
+impl<'a> Trust<'a> {
+    /// Parses `psk:IDENTITY:HEXKEY` or `pin:HEXSHA256`
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let value = value.trim();
+
+        if let Some(hex) = value.strip_prefix("pin:") {
+            let pin = from_hex::<32>(hex)?;
+            return pin.into_array().ok().map(Trust::Pin);
+        }
+
+        let (identity, hex) = value.strip_prefix("psk:")?.rsplit_once(':')?;
+        if identity.is_empty() {
+            return None;
+        }
+
+        Some(Trust::Psk {
+            identity,
+            key: from_hex(hex)?,
+        })
+    }
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<Vec<u8, N>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let mut bytes = Vec::new();
+    for pair in hex.as_bytes().chunks(2) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        bytes.push(u8::from_str_radix(pair, 16).ok()?).ok()?;
+    }
+
+    Some(bytes)
+}
+
+pub fn is_https(url: &str) -> bool {
+    url.get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+/// Splits the first DER element off `der`, returning its tag, its contents
+/// and what follows it
+fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = match first {
+        0..=0x7f => (first as usize, rest),
+        // Long form, a certificate never needs more than 2 length bytes
+        0x81..=0x82 => {
+            let (bytes, rest) = rest.split_at_checked((first & 0x7f) as usize)?;
+            let len = bytes.iter().fold(0, |len, b| len << 8 | *b as usize);
+            (len, rest)
+        }
+        _ => return None,
+    };
+
+    let (contents, rest) = rest.split_at_checked(len)?;
+
+    Some((tag, contents, rest))
+}
+
+/// The DER encoded SubjectPublicKeyInfo of an X.509 certificate, tag and
+/// length included, which is what a [`Pin`] is the hash of
+pub fn public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (DER_SEQUENCE, certificate, _) = der_element(certificate)? else {
+        return None;
+    };
+    let (DER_SEQUENCE, mut fields, _) = der_element(certificate)? else {
+        return None;
+    };
+
+    if fields.first() == Some(&DER_VERSION) {
+        fields = der_element(fields)?.2;
+    }
+
+    // Serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        fields = der_element(fields)?.2;
+    }
+
+    let (DER_SEQUENCE, _, rest) = der_element(fields)? else {
+        return None;
+    };
+
+    Some(&fields[..fields.len() - rest.len()])
+}
+
+/// The uncompressed point of a P-256 public key, from its
+/// SubjectPublicKeyInfo
+pub fn p256_public_key(public_key_info: &[u8]) -> Option<&[u8]> {
+    let (DER_SEQUENCE, public_key_info, _) = der_element(public_key_info)? else {
+        return None;
+    };
+    let (DER_SEQUENCE, algorithm, rest) = der_element(public_key_info)? else {
+        return None;
+    };
+    let (DER_BIT_STRING, key, _) = der_element(rest)? else {
+        return None;
+    };
+
+    // No unused bits, then 0x04 and both coordinates
+    match key {
+        [0, point @ ..] if algorithm == EC_P256_ALGORITHM && point.len() == 65 && point[0] == 4 => {
+            Some(point)
+        }
+        _ => None,
+    }
+}
+
+/// The message a TLS 1.3 server signs to prove it holds the certificate's
+/// key (RFC 8446, 4.4.3), from the SHA-256 transcript hash up to and
+/// including its Certificate
+pub fn certificate_verify_message(transcript_hash: &[u8; 32]) -> Vec<u8, CERTIFICATE_VERIFY_LEN> {
+    let mut message = Vec::new();
+    message.resize(64, b' ').ok();
+    message.extend_from_slice(CERTIFICATE_VERIFY_CONTEXT).ok();
+    message.extend_from_slice(transcript_hash).ok();
+    message
+}
+
+/// embedded-tls crypto for a server authenticated by its pinned key.
+/// Check [`PinnedProvider::verified`] once the connection is open, as a
+/// server that sends no certificate is never shown to the verifier.
+pub struct PinnedProvider<'p, R> {
+    rng: R,
+    verifier: PinVerifier<'p>,
+}
+
+impl<'p, R: CryptoRngCore> PinnedProvider<'p, R> {
+    pub fn new(rng: R, pin: &'p Pin) -> Self {
+        Self {
+            rng,
+            verifier: PinVerifier {
+                pin,
+                key: None,
+                verified: false,
+            },
+        }
+    }
+
+    /// Whether the server proved it holds the pinned key
+    pub fn verified(&self) -> bool {
+        self.verifier.verified
+    }
+}
+
+impl<R: CryptoRngCore> CryptoProvider for PinnedProvider<'_, R> {
+    type CipherSuite = Aes128GcmSha256;
+    type Signature = DerSignature;
+
+    fn rng(&mut self) -> impl CryptoRngCore {
+        &mut self.rng
+    }
+
+    fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Aes128GcmSha256>, TlsError> {
+        Ok(&mut self.verifier)
+    }
+}
+
+/// Accepts a server whose certificate has the pinned public key, once it
+/// has signed the handshake with it. The name, dates and issuer of the
+/// certificate aren't looked at: the key is what's trusted.
+struct PinVerifier<'p> {
+    pin: &'p Pin,
+    /// Key from the certificate and the transcript it was received with,
+    /// kept for checking the CertificateVerify that follows
+    key: Option<(VerifyingKey, Sha256)>,
+    verified: bool,
+}
+
+impl TlsVerifier<Aes128GcmSha256> for PinVerifier<'_> {
+    // The pin says which server this is, whatever name it was reached by
+    fn set_hostname_verification(&mut self, _hostname: &str) -> Result<(), TlsError> {
+        Ok(())
+    }
+
+    fn verify_certificate(
+        &mut self,
+        transcript: &Sha256,
+        certificate: CertificateRef,
+    ) -> Result<(), TlsError> {
+        let Some(CertificateEntryRef::X509(leaf)) = certificate.entries.first() else {
+            return Err(TlsError::InvalidCertificate);
+        };
+
+        let public_key_info = public_key_info(leaf).ok_or(TlsError::InvalidCertificate)?;
+
+        if Sha256::digest(public_key_info).as_slice() != self.pin {
+            return Err(TlsError::InvalidCertificate);
+        }
+
+        // The pin matched, so only a key that isn't P-256 gets here
+        let key = p256_public_key(public_key_info)
+            .and_then(|point| VerifyingKey::from_sec1_bytes(point).ok())
+            .ok_or(TlsError::InvalidCertificate)?;
+
+        self.key = Some((key, transcript.clone()));
+        Ok(())
+    }
+
+    fn verify_signature(&mut self, verify: CertificateVerifyRef) -> Result<(), TlsError> {
+        let (key, transcript) = self.key.take().ok_or(TlsError::InvalidSignature)?;
+
+        if verify.signature_scheme != SignatureScheme::EcdsaSecp256r1Sha256 {
+            return Err(TlsError::InvalidSignatureScheme);
+        }
+
+        let signature =
+            Signature::from_der(verify.signature).map_err(|_| TlsError::InvalidSignature)?;
+        let message = certificate_verify_message(&transcript.finalize().into());
+
+        key.verify(&message, &signature)
+            .map_err(|_| TlsError::InvalidSignature)?;
+
+        self.verified = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERTIFICATE: &[u8] = include_bytes!("../tests/tls/localhost.der");
+
+    #[test]
+    fn parses_trust() {
+        assert_eq!(
+            Trust::parse("psk:badger:00ff10"),
+            Some(Trust::Psk {
+                identity: "badger",
+                key: Vec::from_slice(&[0x00, 0xff, 0x10]).unwrap(),
+            })
+        );
+
+        let mut pin = [0x11; 32];
+        pin[31] = 0xab;
+        assert_eq!(
+            Trust::parse(" pin:11111111111111111111111111111111111111111111111111111111111111ab "),
+            Some(Trust::Pin(pin))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_trust() {
+        assert_eq!(Trust::parse(""), None);
+        assert_eq!(Trust::parse("insecure"), None);
+        assert_eq!(Trust::parse("psk:badger"), None);
+        assert_eq!(Trust::parse("psk::00ff"), None);
+        assert_eq!(Trust::parse("psk:badger:0ff"), None);
+        assert_eq!(Trust::parse("psk:badger:zz"), None);
+        assert_eq!(Trust::parse("pin:abcd"), None);
+        assert_eq!(Trust::parse(&std::format!("pin:{}", "ab".repeat(33))), None);
+    }
+
+    #[test]
+    fn detects_https() {
+        assert!(is_https("https://api.open-meteo.com/v1/forecast"));
+        assert!(is_https("HTTPS://example.com"));
+        assert!(!is_https("http://api.open-meteo.com/v1/forecast"));
+        assert!(!is_https("https:"));
+    }
+
+    #[test]
+    fn finds_the_public_key() {
+        let public_key_info = public_key_info(CERTIFICATE).unwrap();
+
+        // What `openssl pkey -pubin -outform der` prints for the key
+        assert_eq!(public_key_info.len(), 91);
+        assert_eq!(public_key_info[..2], [DER_SEQUENCE, 89]);
+        assert_eq!(public_key_info[4..23], *EC_P256_ALGORITHM);
+
+        let point = p256_public_key(public_key_info).unwrap();
+        assert_eq!(point[..3], [0x04, 0xcc, 0xa3]);
+        assert!(public_key_info.ends_with(point));
+    }
+
+    #[test]
+    fn rejects_broken_certificates() {
+        for len in 0..CERTIFICATE.len() {
+            assert_eq!(public_key_info(&CERTIFICATE[..len]), None);
+        }
+
+        let public_key_info = public_key_info(CERTIFICATE).unwrap();
+
+        let mut rsa = public_key_info.to_vec();
+        rsa[10] = 0x01;
+        assert_eq!(p256_public_key(&rsa), None);
+
+        let mut compressed = public_key_info.to_vec();
+        compressed[26] = 0x02;
+        assert_eq!(p256_public_key(&compressed), None);
+    }
+
+    #[test]
+    fn builds_certificate_verify_message() {
+        let message = certificate_verify_message(&[7; 32]);
+
+        assert_eq!(message.len(), 130);
+        assert!(message[..64].iter().all(|b| *b == b' '));
+        assert_eq!(message[64..98], *b"TLS 1.3, server CertificateVerify\0");
+        assert_eq!(message[98..], [7; 32]);
+    }
+}
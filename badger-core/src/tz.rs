@@ -0,0 +1,397 @@
+//! Local time from a POSIX TZ string such as `CST6CDT,M3.2.0,M11.1.0`, so the
+//! RTC can run on UTC and DST changes happen on the device.
+
+use time::{Date, Duration, Month, PrimitiveDateTime, Time, UtcOffset};
+
+/// Default time of day for a transition, 02:00 local
+const DEFAULT_TRANSITION: i32 = 2 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeZone {
+    /// Standard time, in seconds east of UTC
+    std_offset: i32,
+    dst: Option<Dst>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Dst {
+    /// Daylight time, in seconds east of UTC
+    offset: i32,
+    start: Transition,
+    end: Transition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Transition {
+    day: Day,
+    /// Seconds after local midnight, may be negative or past 24h
+    time: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Day {
+    /// `Jn`: day 1-365, February 29th is never counted
+    Julian(u16),
+    /// `n`: zero based day of the year, counting February 29th
+    Ordinal(u16),
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (5 = last) of `month`
+    Weekday { month: Month, week: u8, weekday: u8 },
+}
+
+impl TimeZone {
+    pub const UTC: TimeZone = TimeZone {
+        std_offset: 0,
+        dst: None,
+    };
+
+    /// Parses a POSIX TZ string. A daylight name without rules gets the
+    /// current US rules, like glibc.
+    pub fn parse(tz: &str) -> Option<TimeZone> {
+        let mut p = Parser::new(tz.trim());
+
+        p.name()?;
+        let std_offset = -p.offset()?;
+
+        if p.done() {
+            return Some(TimeZone {
+                std_offset,
+                dst: None,
+            });
+        }
+
+        p.name()?;
+        let offset = match p.peek() {
+            None | Some(b',') => std_offset + 3600,
+            _ => -p.offset()?,
+        };
+
+        let (start, end) = if p.eat(b',') {
+            let start = p.rule()?;
+            if !p.eat(b',') {
+                return None;
+            }
+            (start, p.rule()?)
+        } else {
+            (
+                Transition::month_week_day(Month::March, 2, 0),
+                Transition::month_week_day(Month::November, 1, 0),
+            )
+        };
+
+        if !p.done() {
+            return None;
+        }
+
+        Some(TimeZone {
+            std_offset,
+            dst: Some(Dst { offset, start, end }),
+        })
+    }
+
+    /// Offset from UTC in effect at `utc`
+    pub fn offset_at(&self, utc: PrimitiveDateTime) -> UtcOffset {
+        let seconds = match self.dst {
+            Some(dst) if self.is_dst(&dst, utc) => dst.offset,
+            _ => self.std_offset,
+        };
+
+        UtcOffset::from_whole_seconds(seconds).unwrap_or(UtcOffset::UTC)
+    }
+
+    pub fn to_local(&self, utc: PrimitiveDateTime) -> PrimitiveDateTime {
+        let offset = self.offset_at(utc).whole_seconds();
+
+        utc.checked_add(Duration::seconds(offset.into()))
+            .unwrap_or(utc)
+    }
+
+    fn is_dst(&self, dst: &Dst, utc: PrimitiveDateTime) -> bool {
+        let local_year = utc
+            .checked_add(Duration::seconds(self.std_offset.into()))
+            .unwrap_or(utc)
+            .year();
+
+        // Start is given in standard time, end in daylight time
+        let (Some(start), Some(end)) = (
+            dst.start.to_utc(local_year, self.std_offset),
+            dst.end.to_utc(local_year, dst.offset),
+        ) else {
+            return false;
+        };
+
+        if start < end {
+            start <= utc && utc < end
+        } else {
+            // Southern hemisphere, daylight time spans the new year
+            utc < end || start <= utc
+        }
+    }
+}
+
+impl Transition {
+    const fn month_week_day(month: Month, week: u8, weekday: u8) -> Self {
+        Transition {
+            day: Day::Weekday {
+                month,
+                week,
+                weekday,
+            },
+            time: DEFAULT_TRANSITION,
+        }
+    }
+
+    fn to_utc(self, year: i32, offset: i32) -> Option<PrimitiveDateTime> {
+        let date = self.day.date(year)?;
+
+        PrimitiveDateTime::new(date, Time::MIDNIGHT)
+            .checked_add(Duration::seconds((self.time - offset).into()))
+    }
+}
+
+impl Day {
+    fn date(self, year: i32) -> Option<Date> {
+        let is_leap = Date::from_calendar_date(year, Month::February, 29).is_ok();
+
+        match self {
+            Day::Julian(n) => {
+                let ordinal = if is_leap && n >= 60 { n + 1 } else { n };
+                Date::from_ordinal_date(year, ordinal).ok()
+            }
+            Day::Ordinal(n) => Date::from_ordinal_date(year, n + 1).ok(),
+            Day::Weekday {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = Date::from_calendar_date(year, month, 1).ok()?;
+                let first_weekday = first.weekday().number_days_from_sunday();
+
+                let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+
+                // Week 5 means the last one, which may be the 4th
+                loop {
+                    match Date::from_calendar_date(year, month, day) {
+                        Ok(date) => return Some(date),
+                        Err(_) if day > 7 => day -= 7,
+                        Err(_) => return None,
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Parser {
+            s: s.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let matched = self.peek() == Some(c);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.s.len()
+    }
+
+    /// Consumes bytes while `f` holds, returning how many
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    /// `CST`, or a quoted name such as `<+0330>`
+    fn name(&mut self) -> Option<()> {
+        let len = if self.eat(b'<') {
+            let len = self.take_while(|c| c.is_ascii_alphanumeric() || c == b'+' || c == b'-');
+            self.eat(b'>').then_some(len)?
+        } else {
+            self.take_while(|c| c.is_ascii_alphabetic())
+        };
+
+        (len >= 3).then_some(())
+    }
+
+    fn number(&mut self, max: u32) -> Option<u32> {
+        let start = self.pos;
+        if self.take_while(|c| c.is_ascii_digit()) == 0 {
+            return None;
+        }
+
+        let value = core::str::from_utf8(&self.s[start..self.pos])
+            .ok()?
+            .parse::<u32>()
+            .ok()?;
+
+        (value <= max).then_some(value)
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds
+    fn offset(&mut self) -> Option<i32> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+
+        let mut seconds = self.number(167)? * 3600;
+        if self.eat(b':') {
+            seconds += self.number(59)? * 60;
+            if self.eat(b':') {
+                seconds += self.number(59)?;
+            }
+        }
+
+        Some(sign * seconds as i32)
+    }
+
+    fn rule(&mut self) -> Option<Transition> {
+        let day = if self.eat(b'J') {
+            Day::Julian(self.number(365).filter(|n| *n >= 1)? as u16)
+        } else if self.eat(b'M') {
+            let month = Month::try_from(self.number(12)? as u8).ok()?;
+            self.eat(b'.').then_some(())?;
+            let week = self.number(5).filter(|w| *w >= 1)? as u8;
+            self.eat(b'.').then_some(())?;
+            let weekday = self.number(6)? as u8;
+
+            Day::Weekday {
+                month,
+                week,
+                weekday,
+            }
+        } else {
+            Day::Ordinal(self.number(365)? as u16)
+        };
+
+        let time = if self.eat(b'/') {
+            self.offset()?
+        } else {
+            DEFAULT_TRANSITION
+        };
+
+        Some(Transition { day, time })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: Month, day: u8, hour: u8, minute: u8) -> PrimitiveDateTime {
+        PrimitiveDateTime::new(
+            Date::from_calendar_date(year, month, day).unwrap(),
+            Time::from_hms(hour, minute, 0).unwrap(),
+        )
+    }
+
+    fn hours(tz: &TimeZone, at: PrimitiveDateTime) -> i32 {
+        tz.offset_at(at).whole_seconds() / 3600
+    }
+
+    #[test]
+    fn fixed_offsets() {
+        let india = TimeZone::parse("IST-5:30").unwrap();
+        let offset = india.offset_at(utc(2026, Month::June, 1, 0, 0));
+        assert_eq!(offset, UtcOffset::from_hms(5, 30, 0).unwrap());
+
+        let quoted = TimeZone::parse("<-03>3").unwrap();
+        assert_eq!(hours(&quoted, utc(2026, Month::June, 1, 0, 0)), -3);
+
+        assert_eq!(TimeZone::parse("UTC0"), Some(TimeZone::UTC));
+    }
+
+    #[test]
+    fn us_central_transitions() {
+        let tz = TimeZone::parse("CST6CDT,M3.2.0,M11.1.0").unwrap();
+
+        // 2026-03-08 02:00 CST is 08:00 UTC
+        assert_eq!(hours(&tz, utc(2026, Month::March, 8, 7, 59)), -6);
+        assert_eq!(hours(&tz, utc(2026, Month::March, 8, 8, 0)), -5);
+
+        // 2026-11-01 02:00 CDT is 07:00 UTC
+        assert_eq!(hours(&tz, utc(2026, Month::November, 1, 6, 59)), -5);
+        assert_eq!(hours(&tz, utc(2026, Month::November, 1, 7, 0)), -6);
+    }
+
+    #[test]
+    fn daylight_name_without_rules_uses_us_rules() {
+        let tz = TimeZone::parse("EST5EDT").unwrap();
+
+        assert_eq!(hours(&tz, utc(2026, Month::July, 1, 12, 0)), -4);
+        assert_eq!(hours(&tz, utc(2026, Month::January, 1, 12, 0)), -5);
+    }
+
+    #[test]
+    fn southern_hemisphere() {
+        let tz = TimeZone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+
+        assert_eq!(hours(&tz, utc(2026, Month::January, 15, 0, 0)), 11);
+        assert_eq!(hours(&tz, utc(2026, Month::July, 15, 0, 0)), 10);
+        assert_eq!(hours(&tz, utc(2026, Month::December, 31, 20, 0)), 11);
+    }
+
+    #[test]
+    fn last_week_of_month() {
+        // Europe: last Sunday of March at 01:00 UTC
+        let tz = TimeZone::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+
+        assert_eq!(hours(&tz, utc(2026, Month::March, 29, 0, 59)), 0);
+        assert_eq!(hours(&tz, utc(2026, Month::March, 29, 1, 0)), 1);
+        assert_eq!(hours(&tz, utc(2026, Month::October, 25, 0, 59)), 1);
+        assert_eq!(hours(&tz, utc(2026, Month::October, 25, 1, 0)), 0);
+    }
+
+    #[test]
+    fn julian_and_ordinal_days() {
+        // J60 is always March 1st, 59 is Feb 29th in leap years
+        assert_eq!(
+            Day::Julian(60).date(2028),
+            Date::from_calendar_date(2028, Month::March, 1).ok()
+        );
+        assert_eq!(
+            Day::Ordinal(59).date(2028),
+            Date::from_calendar_date(2028, Month::February, 29).ok()
+        );
+    }
+
+    #[test]
+    fn converts_to_local() {
+        let tz = TimeZone::parse("CST6CDT,M3.2.0,M11.1.0").unwrap();
+        let local = tz.to_local(utc(2026, Month::January, 1, 3, 0));
+
+        assert_eq!(local, utc(2025, Month::December, 31, 21, 0));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert_eq!(TimeZone::parse(""), None);
+        assert_eq!(TimeZone::parse("CST"), None);
+        assert_eq!(TimeZone::parse("C6"), None);
+        assert_eq!(TimeZone::parse("CST6CDT,M3.2.0"), None);
+        assert_eq!(TimeZone::parse("CST6CDT,M13.2.0,M11.1.0"), None);
+        assert_eq!(TimeZone::parse("CST6CDT,M3.6.0,M11.1.0"), None);
+        assert_eq!(TimeZone::parse("<+0330-3:30"), None);
+        assert_eq!(TimeZone::parse("CST6 trailing"), None);
+    }
+}
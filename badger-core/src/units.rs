@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+/// Unit system the weather is requested and shown in
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Units {
+    /// °C, km/h, mm
+    #[default]
+    Metric,
+    /// °F, mph, inch
+    Imperial,
+}
+
+impl Units {
+    /// `metric` or `imperial`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "metric" => Some(Units::Metric),
+            "imperial" => Some(Units::Imperial),
+            _ => None,
+        }
+    }
+
+    /// Open-Meteo query parameters asking for these units
+    pub fn open_meteo_params(self) -> &'static str {
+        match self {
+            Units::Metric => "temperature_unit=celsius&wind_speed_unit=kmh&precipitation_unit=mm",
+            Units::Imperial => {
+                "temperature_unit=fahrenheit&wind_speed_unit=mph&precipitation_unit=inch"
+            }
+        }
+    }
+
+    /// Suffix for temperatures, without a degree sign since the fonts lack one
+    pub fn temperature_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "C",
+            Units::Imperial => "F",
+        }
+    }
+
+    pub fn wind_speed_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+
+    pub fn precipitation_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "mm",
+            Units::Imperial => "in",
+        }
+    }
+
+    /// Converts a wind speed from a provider that only reports m/s
+    pub fn from_metres_per_second(self, speed: f32) -> f32 {
+        match self {
+            Units::Metric => speed * 3.6,
+            Units::Imperial => speed * 2.236_936,
+        }
+    }
+
+    /// Converts precipitation from a provider that only reports mm
+    pub fn from_millimetres(self, amount: f32) -> f32 {
+        match self {
+            Units::Metric => amount,
+            Units::Imperial => amount / 25.4,
+        }
+    }
+
+    /// Converts a temperature from a provider that only reports °C
+    pub fn from_celsius(self, celsius: f32) -> f32 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(Units::parse("metric"), Some(Units::Metric));
+        assert_eq!(Units::parse(" imperial "), Some(Units::Imperial));
+        assert_eq!(Units::parse("kelvin"), None);
+    }
+
+    #[test]
+    fn converts_celsius() {
+        assert_eq!(Units::Metric.from_celsius(-3.5), -3.5);
+        assert_eq!(Units::Imperial.from_celsius(100.0), 212.0);
+        assert_eq!(Units::Imperial.from_celsius(-40.0), -40.0);
+    }
+
+    #[test]
+    fn converts_wind_and_rain() {
+        assert_eq!(Units::Metric.from_metres_per_second(10.0), 36.0);
+        assert!((Units::Imperial.from_metres_per_second(10.0) - 22.37).abs() < 0.01);
+        assert_eq!(Units::Imperial.from_millimetres(25.4), 1.0);
+    }
+}
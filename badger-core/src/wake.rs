@@ -0,0 +1,522 @@
+use time::{Duration, PrimitiveDateTime};
+
+use crate::{
+    image::Shift,
+    layout::{Page, Screen},
+    schedule::{is_catch_up_time, is_sync_time},
+};
+
+/// Bits of the RTC RAM byte that remember the page, the rest hold the image
+/// index
+const PAGE_MASK: u8 = 0xe0;
+const PAGE_SHIFT: u8 = 5;
+
+/// Inputs sampled right after the power latch is set. More than one can be
+/// high at once, e.g. a button held while the RTC alarm fires.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WakeCause {
+    pub up: bool,
+    pub down: bool,
+    pub a: bool,
+    pub b: bool,
+    pub c: bool,
+    pub rtc_alarm: bool,
+}
+
+impl WakeCause {
+    /// Nothing we know about is high, e.g. a reset or a fresh battery
+    pub fn is_unknown(&self) -> bool {
+        *self == WakeCause::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alarm {
+    /// Second 0 of every minute, to tick the clock
+    EveryMinute,
+    /// The top of every local hour, for the sync only
+    EveryHour,
+}
+
+/// What to do before going back to sleep
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WakePlan {
+    pub screen: Screen,
+    pub sync_wifi: bool,
+    pub image_shift: Shift,
+    /// Page to show from now on
+    pub page: Page,
+    /// Alarm to arm before sleeping, assuming a planned sync sets the clock
+    pub next_alarm: Alarm,
+    /// Start the setup access point before the sync, see
+    /// [`crate::provision`]
+    pub provision: bool,
+}
+
+/// Decides what a wake should do. Every input contributes, so simultaneous
+/// causes combine instead of the first one winning:
+///
+/// * Up / Down move the image, going back to the image page; both together
+///   cancel out
+/// * A syncs and refreshes the top bar, B redraws everything, C switches
+///   to the next of the image, forecast, hourly, details and air quality
+///   pages, unless Up / Down is also held
+/// * A and B together are a chord instead: they start setup, then sync with
+///   whatever it saved and redraw everything
+/// * The RTC alarm refreshes the clock, or syncs and redraws everything on
+///   the hour, and on the catch-up minutes after a failed sync (see
+///   [`is_catch_up_time`])
+/// * An unknown wake redraws everything, and syncs if the clock isn't set
+///
+/// `now` is the RTC time converted to local time, so the sync lands on the
+/// local hour. It still counts whole hours even before it has been set from
+/// the network. `since_sync` is the time since the last successful sync, if
+/// both it and the clock are known.
+pub fn plan(
+    cause: WakeCause,
+    now: Option<PrimitiveDateTime>,
+    time_trusted: bool,
+    page: Page,
+    since_sync: Option<Duration>,
+) -> WakePlan {
+    let image_shift = match (cause.up, cause.down) {
+        (true, false) => Shift::Prev,
+        (false, true) => Shift::Next,
+        _ => Shift::None,
+    };
+
+    let mut screen = Screen::None;
+    let mut sync_wifi = false;
+    let mut page = page;
+
+    let provision = cause.a && cause.b;
+    if provision {
+        sync_wifi = true;
+        screen = Screen::Full;
+    }
+
+    if image_shift != Shift::None {
+        page = Page::Image;
+        screen = merge(screen, Screen::Image);
+    }
+
+    if cause.a && !provision {
+        sync_wifi = true;
+        screen = merge(screen, Screen::TopBar);
+    }
+
+    if cause.b && !provision {
+        screen = merge(screen, Screen::Full);
+    }
+
+    if cause.c && image_shift == Shift::None {
+        page = page.next();
+        screen = merge(screen, page.screen());
+    }
+
+    if cause.rtc_alarm {
+        if now.is_some_and(is_sync_time) || since_sync.is_some_and(is_catch_up_time) {
+            sync_wifi = true;
+            screen = merge(screen, Screen::Full);
+        } else {
+            screen = merge(screen, Screen::TopBar);
+        }
+    }
+
+    if cause.is_unknown() {
+        sync_wifi = !time_trusted;
+        screen = Screen::Full;
+    }
+
+    // Without a trusted clock there's nothing to tick every minute
+    let next_alarm = if time_trusted || sync_wifi {
+        Alarm::EveryMinute
+    } else {
+        Alarm::EveryHour
+    };
+
+    WakePlan {
+        screen,
+        sync_wifi,
+        image_shift,
+        page,
+        next_alarm,
+        provision,
+    }
+}
+
+/// Packs the image index and page into the RTC's one byte of RAM
+pub fn to_ram_byte(image: usize, page: Page) -> u8 {
+    let page = match page {
+        Page::Image => 0,
+        Page::Forecast => 1,
+        Page::Hourly => 2,
+        Page::Details => 3,
+        Page::AirQuality => 4,
+    };
+
+    (image as u8 & !PAGE_MASK) | (page << PAGE_SHIFT)
+}
+
+/// Reverse of [`to_ram_byte`]
+pub fn from_ram_byte(byte: u8) -> (usize, Page) {
+    let page = match (byte & PAGE_MASK) >> PAGE_SHIFT {
+        1 => Page::Forecast,
+        2 => Page::Hourly,
+        3 => Page::Details,
+        4 => Page::AirQuality,
+        _ => Page::Image,
+    };
+
+    ((byte & !PAGE_MASK) as usize, page)
+}
+
+/// Smallest refresh that covers both `a` and `b`
+fn merge(a: Screen, b: Screen) -> Screen {
+    match (a, b) {
+        (Screen::None, x) | (x, Screen::None) => x,
+        (x, y) if x == y => x,
+        _ => Screen::Full,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month, Time};
+
+    fn at(hour: u8, minute: u8) -> Option<PrimitiveDateTime> {
+        Some(PrimitiveDateTime::new(
+            Date::from_calendar_date(2026, Month::January, 1).unwrap(),
+            Time::from_hms(hour, minute, 0).unwrap(),
+        ))
+    }
+
+    fn alarm() -> WakeCause {
+        WakeCause {
+            rtc_alarm: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn single_buttons() {
+        let up = plan(
+            WakeCause {
+                up: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(up.image_shift, Shift::Prev);
+        assert_eq!(up.screen, Screen::Image);
+        assert!(!up.sync_wifi);
+
+        let down = plan(
+            WakeCause {
+                down: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(down.image_shift, Shift::Next);
+        assert_eq!(down.screen, Screen::Image);
+
+        let a = plan(
+            WakeCause {
+                a: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert!(a.sync_wifi);
+        assert_eq!(a.screen, Screen::TopBar);
+
+        let b = plan(
+            WakeCause {
+                b: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert!(!b.sync_wifi);
+        assert_eq!(b.screen, Screen::Full);
+
+        let c = plan(
+            WakeCause {
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(c.screen, Screen::Forecast);
+        assert_eq!(c.page, Page::Forecast);
+        assert_eq!(c.image_shift, Shift::None);
+
+        let c_hourly = plan(
+            WakeCause {
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Forecast,
+            None,
+        );
+        assert_eq!(c_hourly.screen, Screen::Hourly);
+        assert_eq!(c_hourly.page, Page::Hourly);
+
+        let c_air = plan(
+            WakeCause {
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Details,
+            None,
+        );
+        assert_eq!(c_air.screen, Screen::AirQuality);
+        assert_eq!(c_air.page, Page::AirQuality);
+
+        let c_back = plan(
+            WakeCause {
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::AirQuality,
+            None,
+        );
+        assert_eq!(c_back.screen, Screen::Image);
+        assert_eq!(c_back.page, Page::Image);
+    }
+
+    #[test]
+    fn rtc_alarm_syncs_on_the_hour() {
+        let tick = plan(alarm(), at(9, 30), true, Page::Image, None);
+        assert_eq!(tick.screen, Screen::TopBar);
+        assert!(!tick.sync_wifi);
+
+        let hourly = plan(alarm(), at(10, 0), true, Page::Image, None);
+        assert_eq!(hourly.screen, Screen::Full);
+        assert!(hourly.sync_wifi);
+
+        let unreadable = plan(alarm(), None, true, Page::Image, None);
+        assert_eq!(unreadable.screen, Screen::TopBar);
+        assert!(!unreadable.sync_wifi);
+    }
+
+    #[test]
+    fn simultaneous_buttons_combine() {
+        let up_and_down = plan(
+            WakeCause {
+                up: true,
+                down: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(up_and_down.image_shift, Shift::None);
+        assert_eq!(up_and_down.screen, Screen::None);
+
+        let down_and_a = plan(
+            WakeCause {
+                down: true,
+                a: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(down_and_a.image_shift, Shift::Next);
+        assert!(down_and_a.sync_wifi);
+        assert_eq!(down_and_a.screen, Screen::Full);
+
+        let a_and_c = plan(
+            WakeCause {
+                a: true,
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(a_and_c.screen, Screen::Full);
+        assert_eq!(a_and_c.page, Page::Forecast);
+        assert!(!a_and_c.provision);
+    }
+
+    #[test]
+    fn a_and_b_start_setup() {
+        let setup = plan(
+            WakeCause {
+                a: true,
+                b: true,
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            false,
+            Page::Details,
+            None,
+        );
+
+        assert!(setup.provision);
+        assert!(setup.sync_wifi);
+        assert_eq!(setup.screen, Screen::Full);
+        assert_eq!(setup.page, Page::AirQuality);
+        assert_eq!(setup.next_alarm, Alarm::EveryMinute);
+    }
+
+    #[test]
+    fn image_buttons_leave_the_forecast() {
+        let down = plan(
+            WakeCause {
+                down: true,
+                c: true,
+                ..Default::default()
+            },
+            at(9, 30),
+            true,
+            Page::Forecast,
+            None,
+        );
+        assert_eq!(down.page, Page::Image);
+        assert_eq!(down.screen, Screen::Image);
+        assert_eq!(down.image_shift, Shift::Next);
+
+        let hourly = plan(alarm(), at(10, 0), true, Page::Forecast, None);
+        assert_eq!(hourly.page, Page::Forecast);
+        assert_eq!(hourly.screen, Screen::Full);
+    }
+
+    #[test]
+    fn ram_byte_round_trips() {
+        assert_eq!(
+            from_ram_byte(to_ram_byte(2, Page::Forecast)),
+            (2, Page::Forecast)
+        );
+        assert_eq!(from_ram_byte(to_ram_byte(1, Page::Image)), (1, Page::Image));
+        assert_eq!(
+            from_ram_byte(to_ram_byte(0, Page::Hourly)),
+            (0, Page::Hourly)
+        );
+        assert_eq!(
+            from_ram_byte(to_ram_byte(31, Page::Details)),
+            (31, Page::Details)
+        );
+        assert_eq!(
+            from_ram_byte(to_ram_byte(3, Page::AirQuality)),
+            (3, Page::AirQuality)
+        );
+        // Written before the page was stored
+        assert_eq!(from_ram_byte(2), (2, Page::Image));
+    }
+
+    #[test]
+    fn button_during_rtc_alarm_is_not_lost() {
+        let up = plan(
+            WakeCause {
+                up: true,
+                ..alarm()
+            },
+            at(9, 30),
+            true,
+            Page::Image,
+            None,
+        );
+        assert_eq!(up.image_shift, Shift::Prev);
+        assert_eq!(up.screen, Screen::Full);
+        assert!(!up.sync_wifi);
+
+        let c_on_the_hour = plan(
+            WakeCause { c: true, ..alarm() },
+            at(10, 0),
+            true,
+            Page::Image,
+            None,
+        );
+        assert!(c_on_the_hour.sync_wifi);
+        assert_eq!(c_on_the_hour.screen, Screen::Full);
+    }
+
+    #[test]
+    fn retries_a_failed_sync_on_minute_wakes() {
+        let since = |minutes| Some(Duration::minutes(minutes));
+
+        let catch_up = plan(alarm(), at(11, 5), true, Page::Image, since(65));
+        assert!(catch_up.sync_wifi);
+        assert_eq!(catch_up.screen, Screen::Full);
+
+        let between = plan(alarm(), at(11, 6), true, Page::Image, since(66));
+        assert!(!between.sync_wifi);
+        assert_eq!(between.screen, Screen::TopBar);
+
+        // Only alarms retry, a button press doesn't turn into a sync
+        let button = plan(
+            WakeCause {
+                b: true,
+                ..Default::default()
+            },
+            at(11, 5),
+            true,
+            Page::Image,
+            since(65),
+        );
+        assert!(!button.sync_wifi);
+    }
+
+    #[test]
+    fn unknown_wake() {
+        let trusted = plan(WakeCause::default(), at(9, 30), true, Page::Image, None);
+        assert_eq!(trusted.screen, Screen::Full);
+        assert!(!trusted.sync_wifi);
+        assert_eq!(trusted.image_shift, Shift::None);
+
+        let untrusted = plan(WakeCause::default(), at(9, 30), false, Page::Image, None);
+        assert_eq!(untrusted.screen, Screen::Full);
+        assert!(untrusted.sync_wifi);
+    }
+
+    #[test]
+    fn untrusted_clock_only_wakes_hourly() {
+        assert_eq!(
+            plan(alarm(), at(9, 30), false, Page::Image, None).next_alarm,
+            Alarm::EveryHour
+        );
+        assert_eq!(
+            plan(alarm(), at(9, 30), true, Page::Image, None).next_alarm,
+            Alarm::EveryMinute
+        );
+        assert_eq!(
+            plan(alarm(), at(9, 0), false, Page::Image, None).next_alarm,
+            Alarm::EveryMinute
+        );
+    }
+}
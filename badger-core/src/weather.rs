@@ -0,0 +1,277 @@
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+use time::PrimitiveDateTime;
+
+/// Days shown on the forecast page, today included
+pub const FORECAST_DAYS: usize = 4;
+
+/// Hours plotted on the hourly page, starting with the current one
+pub const FORECAST_HOURS: usize = 24;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct CurrentWeather {
+    pub temperature: f32,
+    pub weathercode: u8,
+    pub relative_humidity_2m: f32,
+    /// 1 between sunrise and sunset, 0 otherwise
+    pub is_day: u8,
+    // The rest are only on the details page, and not every provider has them
+    pub apparent_temperature: Option<f32>,
+    pub wind_speed_10m: Option<f32>,
+    /// Degrees clockwise from north that the wind blows from
+    pub wind_direction_10m: Option<f32>,
+    /// Recent precipitation, in mm or inches
+    pub precipitation: Option<f32>,
+    pub uv_index: Option<f32>,
+}
+
+impl CurrentWeather {
+    /// Open-Meteo `current=` variables, one per field above
+    pub const FIELDS: &'static [&'static str] = &[
+        "temperature",
+        "weathercode",
+        "relative_humidity_2m",
+        "is_day",
+        "apparent_temperature",
+        "wind_speed_10m",
+        "wind_direction_10m",
+        "precipitation",
+        "uv_index",
+    ];
+
+    pub fn is_daytime(&self) -> bool {
+        self.is_day != 0
+    }
+}
+
+/// One day of the forecast page
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct DailyForecast {
+    /// Days from Monday, 0-6
+    pub weekday: u8,
+    pub high: f32,
+    pub low: f32,
+    pub weathercode: u8,
+    /// Highest chance of precipitation during the day, if known
+    pub precipitation_probability: Option<u8>,
+}
+
+impl DailyForecast {
+    /// Open-Meteo `daily=` variables, one per field above
+    pub const FIELDS: &'static [&'static str] = &[
+        "temperature_2m_max",
+        "temperature_2m_min",
+        "weathercode",
+        "precipitation_probability_max",
+    ];
+}
+
+pub type Forecast = Vec<DailyForecast, FORECAST_DAYS>;
+
+/// The next hours of the hourly page, one entry per hour
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HourlyForecast {
+    /// Local hour of the first entry, 0-23
+    pub start_hour: u8,
+    pub temperature: Vec<f32, FORECAST_HOURS>,
+    /// Chance of precipitation in percent, 0 where unknown
+    pub precipitation_probability: Vec<u8, FORECAST_HOURS>,
+}
+
+impl HourlyForecast {
+    /// Open-Meteo `hourly=` variables, one per series above
+    pub const FIELDS: &'static [&'static str] = &["temperature_2m", "precipitation_probability"];
+}
+
+/// Everything a provider returns from one request
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeatherReport {
+    pub current: CurrentWeather,
+    /// `None` for providers without daily data
+    pub forecast: Option<Forecast>,
+    /// `None` for providers without hourly data
+    pub hourly: Option<HourlyForecast>,
+}
+
+/// How old the current weather may get before it is marked, then hidden
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxAge {
+    /// Hours after which the top bar shows the age in place of the humidity
+    pub stale_hours: u16,
+    /// Hours after which the weather isn't shown at all
+    pub hidden_hours: u16,
+}
+
+impl Default for MaxAge {
+    fn default() -> Self {
+        Self {
+            stale_hours: 3,
+            hidden_hours: 24,
+        }
+    }
+}
+
+/// Whether the current weather can still be shown as current
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Freshness {
+    /// Recent, or of unknown age
+    #[default]
+    Fresh,
+    /// Fetched this many whole hours ago
+    Stale(u16),
+    /// Too old to show
+    Expired,
+}
+
+impl MaxAge {
+    /// Compares the UTC time the weather was fetched with the current one.
+    /// Weather without a fetch time, from before it was recorded, or read
+    /// while the clock isn't set, gets the benefit of the doubt.
+    pub fn freshness(
+        &self,
+        fetched: Option<PrimitiveDateTime>,
+        now: Option<PrimitiveDateTime>,
+    ) -> Freshness {
+        match fetched.zip(now) {
+            Some((fetched, now)) => self.for_age((now - fetched).whole_hours()),
+            None => Freshness::Fresh,
+        }
+    }
+
+    /// Freshness of weather fetched `hours` ago. A negative age means the
+    /// clock went backwards, and tells us nothing.
+    pub fn for_age(&self, hours: i64) -> Freshness {
+        match hours {
+            h if h >= self.hidden_hours.into() => Freshness::Expired,
+            h if h >= self.stale_hours.into() => Freshness::Stale(h as u16),
+            _ => Freshness::Fresh,
+        }
+    }
+}
+
+/// One of eight compass points, e.g. `NW` for 315°
+pub fn compass_point(degrees: f32) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+    // Tenths of a degree, as `f32::rem_euclid` needs std
+    let tenths = (degrees * 10.0) as i32;
+    let index = (tenths.rem_euclid(3600) + 225) / 450;
+
+    POINTS[index as usize % POINTS.len()]
+}
+
+pub fn weekday_name(weekday: u8) -> &'static str {
+    match weekday {
+        0 => "Mon",
+        1 => "Tue",
+        2 => "Wed",
+        3 => "Thu",
+        4 => "Fri",
+        5 => "Sat",
+        6 => "Sun",
+        _ => "?",
+    }
+}
+
+/// Fits a forecast column, where `weather_description` can be too wide
+pub fn short_description(code: u8) -> &'static str {
+    match code {
+        0 | 1 => "Clear",
+        2 => "Partly",
+        3 => "Cloudy",
+        45..=48 => "Fog",
+        51..=57 => "Drizzle",
+        61..=67 | 80..=82 => "Rain",
+        71..=77 | 85 | 86 => "Snow",
+        95..=99 => "Storm",
+        _ => "?",
+    }
+}
+
+pub fn weather_description(code: u8) -> &'static str {
+    match code {
+        0 => "Clear",
+        1 => "Mainly Clear",
+        2 => "Partly Cloudy",
+        3 => "Cloudy",
+        45..=48 => "Fog",
+        51..=55 => "Drizzle",
+        56 | 57 => "Freezing Drizzle",
+        61 => "Light Rain",
+        63 => "Rain",
+        65 => "Heavy Rain",
+        66 | 67 => "Freezing Rain",
+        71 => "Light Snow",
+        73 => "Snow",
+        75 => "Heavy Snow",
+        77 => "Snow Grains",
+        80..=82 => "Rain Showers",
+        85 | 86 => "Snow Showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Hailstorm",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_code_ranges() {
+        assert_eq!(weather_description(0), "Clear");
+        assert_eq!(weather_description(48), "Fog");
+        assert_eq!(weather_description(53), "Drizzle");
+        assert_eq!(weather_description(81), "Rain Showers");
+        assert_eq!(weather_description(99), "Hailstorm");
+    }
+
+    #[test]
+    fn short_descriptions() {
+        assert_eq!(short_description(2), "Partly");
+        assert_eq!(short_description(66), "Rain");
+        assert_eq!(short_description(86), "Snow");
+        assert_eq!(short_description(200), "?");
+    }
+
+    #[test]
+    fn compass_points() {
+        assert_eq!(compass_point(0.0), "N");
+        assert_eq!(compass_point(22.4), "N");
+        assert_eq!(compass_point(22.5), "NE");
+        assert_eq!(compass_point(315.0), "NW");
+        assert_eq!(compass_point(359.0), "N");
+        assert_eq!(compass_point(-90.0), "W");
+    }
+
+    #[test]
+    fn weather_goes_stale_then_expires() {
+        use time::{Date, Duration, Month, Time};
+
+        let fetched = PrimitiveDateTime::new(
+            Date::from_calendar_date(2026, Month::January, 1).unwrap(),
+            Time::from_hms(23, 30, 0).unwrap(),
+        );
+        let max_age = MaxAge::default();
+        let after =
+            |minutes| max_age.freshness(Some(fetched), Some(fetched + Duration::minutes(minutes)));
+
+        assert_eq!(after(0), Freshness::Fresh);
+        assert_eq!(after(179), Freshness::Fresh);
+        assert_eq!(after(180), Freshness::Stale(3));
+        assert_eq!(after(23 * 60 + 59), Freshness::Stale(23));
+        assert_eq!(after(24 * 60), Freshness::Expired);
+
+        // Unknown ages and clocks that went backwards don't hide anything
+        assert_eq!(after(-600), Freshness::Fresh);
+        assert_eq!(max_age.freshness(None, Some(fetched)), Freshness::Fresh);
+        assert_eq!(max_age.freshness(Some(fetched), None), Freshness::Fresh);
+    }
+
+    #[test]
+    fn unknown_codes() {
+        assert_eq!(weather_description(4), "Unknown");
+        assert_eq!(weather_description(255), "Unknown");
+    }
+}
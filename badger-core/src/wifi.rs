@@ -0,0 +1,379 @@
+//! Choosing which of the known WiFi networks to join. The firmware scans,
+//! feeds every access point it hears to a [`Scan`], then tries the networks
+//! in [`Scan::join_order`] until one accepts it. Once one has, a
+//! [`Reconnect`] lets the next wake skip the scan and DHCP.
+
+use heapless::{String, Vec};
+use serde::{Deserialize, Serialize};
+
+/// Networks that can be configured, the rest are ignored
+pub const MAX_NETWORKS: usize = 8;
+
+/// Longest SSID 802.11 allows, in bytes
+pub const SSID_LEN: usize = 32;
+
+/// Weakest signal worth trying a network on before the others that were
+/// seen, in dBm
+pub const MIN_USABLE_RSSI: i16 = -80;
+
+/// DNS servers embassy-net keeps from DHCP
+pub const MAX_DNS_SERVERS: usize = 3;
+
+/// How long a DHCP lease is reused without asking again. Routers commonly
+/// hand out a day, and we never renew, so stay well inside that.
+pub const MAX_LEASE_AGE_SECONDS: i64 = 12 * 3600;
+
+/// One set of credentials. An empty password is an open network.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Network<'a> {
+    pub ssid: &'a str,
+    pub password: &'a [u8],
+}
+
+/// Pairs a comma separated list of SSIDs, highest priority first, with one
+/// password per line. Missing passwords are taken to be open networks, as
+/// is a blank line. A lone password without a trailing newline still works,
+/// as written by `echo -n`.
+pub fn parse_networks<'a>(ssids: &'a str, passwords: &'a [u8]) -> Vec<Network<'a>, MAX_NETWORKS> {
+    let mut passwords = passwords
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+    ssids
+        .split(',')
+        .map(str::trim)
+        .map(|ssid| Network {
+            ssid,
+            password: passwords.next().unwrap_or_default(),
+        })
+        .filter(|network| !network.ssid.is_empty() && network.ssid.len() <= SSID_LEN)
+        .take(MAX_NETWORKS)
+        .collect()
+}
+
+/// One radio of a network, as a scan reports it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AccessPoint {
+    pub bssid: [u8; 6],
+    pub channel: u8,
+}
+
+/// Strongest signal heard from each known network during a scan
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Scan {
+    rssi: [Option<i16>; MAX_NETWORKS],
+    access_points: [Option<AccessPoint>; MAX_NETWORKS],
+}
+
+impl Scan {
+    /// Notes an access point, if it belongs to one of `networks`
+    pub fn record(&mut self, networks: &[Network], ssid: &[u8], rssi: i16, ap: AccessPoint) {
+        let seen = self.rssi.iter_mut().zip(&mut self.access_points);
+
+        for ((seen, best_ap), network) in seen.zip(networks) {
+            if network.ssid.as_bytes() == ssid && seen.is_none_or(|best| rssi > best) {
+                *seen = Some(rssi);
+                *best_ap = Some(ap);
+            }
+        }
+    }
+
+    /// The strongest access point heard for `networks[index]`
+    pub fn access_point(&self, index: usize) -> Option<AccessPoint> {
+        self.access_points.get(index).copied().flatten()
+    }
+
+    /// Indexes into `networks` in the order to try them:
+    ///
+    /// 1. `last_good`, the network that worked last time, if it was heard
+    /// 2. networks heard at a usable strength, by priority
+    /// 3. networks heard only faintly, strongest first
+    /// 4. networks the scan missed, by priority, as hidden SSIDs don't show
+    ///    up in scans
+    ///
+    /// When the scan heard nothing at all, e.g. because it failed, that is
+    /// `last_good` followed by the rest in priority order.
+    pub fn join_order(
+        &self,
+        networks: &[Network],
+        last_good: Option<&str>,
+    ) -> Vec<usize, MAX_NETWORKS> {
+        let count = networks.len().min(MAX_NETWORKS);
+        let heard_any = self.rssi.iter().any(Option::is_some);
+
+        let rank = |i: usize| {
+            let heard = self.rssi[i];
+
+            if last_good == Some(networks[i].ssid) && (heard.is_some() || !heard_any) {
+                return (0, 0);
+            }
+
+            match heard {
+                Some(rssi) if rssi >= MIN_USABLE_RSSI => (1, 0),
+                Some(rssi) => (2, -rssi),
+                None => (3, 0),
+            }
+        };
+
+        // The index keeps priority order within each group
+        let mut order: Vec<usize, MAX_NETWORKS> = (0..count).collect();
+        order.sort_unstable_by_key(|i| (rank(*i), *i));
+
+        order
+    }
+}
+
+/// Addressing for one network, as DHCP handed it out or as configured
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ipv4Settings {
+    pub address: [u8; 4],
+    pub prefix_len: u8,
+    pub gateway: Option<[u8; 4]>,
+    pub dns_servers: Vec<[u8; 4], MAX_DNS_SERVERS>,
+}
+
+impl Ipv4Settings {
+    /// Parses `address/prefix,gateway,dns,...`, e.g.
+    /// `192.168.1.50/24,192.168.1.1,1.1.1.1`. The gateway can be left
+    /// empty, and up to [`MAX_DNS_SERVERS`] DNS servers follow it.
+    pub fn parse(settings: &str) -> Option<Self> {
+        let mut fields = settings.split(',').map(str::trim);
+
+        let (address, prefix_len) = fields.next()?.split_once('/')?;
+        let prefix_len = prefix_len.parse().ok().filter(|len| *len <= 32)?;
+
+        let gateway = match fields.next() {
+            None | Some("") => None,
+            Some(gateway) => Some(parse_ipv4(gateway)?),
+        };
+
+        let mut dns_servers = Vec::new();
+        for dns in fields {
+            dns_servers.push(parse_ipv4(dns)?).ok()?;
+        }
+
+        Some(Self {
+            address: parse_ipv4(address)?,
+            prefix_len,
+            gateway,
+            dns_servers,
+        })
+    }
+}
+
+/// Dotted quad, e.g. `192.168.1.50`
+fn parse_ipv4(address: &str) -> Option<[u8; 4]> {
+    let mut octets = [0; 4];
+    let mut parts = address.split('.');
+
+    for octet in &mut octets {
+        *octet = parts.next()?.parse().ok()?;
+    }
+
+    parts.next().is_none().then_some(octets)
+}
+
+/// Splits the `;` separated `SSID=settings` list of networks with a static
+/// address, see [`Ipv4Settings::parse`]. Settings that don't parse come back
+/// as `None`, so they can be reported.
+pub fn parse_static_ipv4(config: &str) -> Vec<(&str, Option<Ipv4Settings>), MAX_NETWORKS> {
+    config
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(ssid, settings)| (ssid.trim(), Ipv4Settings::parse(settings)))
+        .take(MAX_NETWORKS)
+        .collect()
+}
+
+/// What the last full join ended up with, so the next wake can join the
+/// same network straight away and reuse its DHCP lease
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reconnect {
+    pub ssid: String<SSID_LEN>,
+    /// The access point it joined through, if the scan heard it
+    pub access_point: Option<AccessPoint>,
+    pub lease: Ipv4Settings,
+    /// When DHCP handed out `lease`, in Unix seconds. Fast reconnects don't
+    /// move it, so a full join comes round every [`MAX_LEASE_AGE_SECONDS`].
+    pub leased_at: i64,
+}
+
+impl Reconnect {
+    /// Index into `networks` to rejoin without scanning, if the network is
+    /// still known and the lease recent. An unknown time is never recent.
+    pub fn usable(&self, networks: &[Network], now: Option<i64>) -> Option<usize> {
+        let age = now? - self.leased_at;
+        if !(0..MAX_LEASE_AGE_SECONDS).contains(&age) {
+            return None;
+        }
+
+        networks
+            .iter()
+            .position(|network| network.ssid == self.ssid.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETWORKS: [Network; 3] = [
+        Network {
+            ssid: "Home",
+            password: b"hunter22",
+        },
+        Network {
+            ssid: "Office",
+            password: b"correct horse",
+        },
+        Network {
+            ssid: "Phone",
+            password: b"",
+        },
+    ];
+
+    fn ap(last: u8) -> AccessPoint {
+        AccessPoint {
+            bssid: [0x02, 0, 0, 0, 0, last],
+            channel: last % 13 + 1,
+        }
+    }
+
+    fn scan(heard: &[(&str, i16)]) -> Scan {
+        let mut scan = Scan::default();
+        for (i, (ssid, rssi)) in heard.iter().enumerate() {
+            scan.record(&NETWORKS, ssid.as_bytes(), *rssi, ap(i as u8));
+        }
+        scan
+    }
+
+    #[test]
+    fn pairs_ssids_with_password_lines() {
+        assert_eq!(
+            parse_networks("Home, Office,Phone", b"hunter22\r\ncorrect horse\n"),
+            NETWORKS
+        );
+        assert_eq!(
+            parse_networks("Home", b"hunter22").as_slice(),
+            &NETWORKS[..1]
+        );
+        assert!(parse_networks("", b"").is_empty());
+        assert_eq!(parse_networks("a,b,c,d,e,f,g,h,i", b"").len(), MAX_NETWORKS);
+    }
+
+    #[test]
+    fn keeps_strongest_access_point() {
+        let scan = scan(&[
+            ("Office", -70),
+            ("Office", -50),
+            ("Cafe", -30),
+            ("Office", -60),
+        ]);
+
+        assert_eq!(scan.rssi[..3], [None, Some(-50), None]);
+        assert_eq!(scan.access_point(1), Some(ap(1)));
+        assert_eq!(scan.access_point(0), None);
+    }
+
+    #[test]
+    fn priority_beats_signal_among_usable_networks() {
+        let order = scan(&[("Phone", -40), ("Home", -75)]).join_order(&NETWORKS, None);
+
+        assert_eq!(order, [0, 2, 1]);
+    }
+
+    #[test]
+    fn faint_networks_go_after_usable_ones() {
+        let order =
+            scan(&[("Home", -90), ("Office", -85), ("Phone", -60)]).join_order(&NETWORKS, None);
+
+        assert_eq!(order, [2, 1, 0]);
+    }
+
+    #[test]
+    fn last_good_network_goes_first_when_heard() {
+        let heard = scan(&[("Home", -50), ("Office", -88)]);
+
+        assert_eq!(heard.join_order(&NETWORKS, Some("Office")), [1, 0, 2]);
+        assert_eq!(heard.join_order(&NETWORKS, Some("Phone")), [0, 1, 2]);
+        assert_eq!(heard.join_order(&NETWORKS, Some("Gone")), [0, 1, 2]);
+    }
+
+    #[test]
+    fn empty_scan_falls_back_to_priority() {
+        let order = Scan::default().join_order(&NETWORKS, Some("Phone"));
+
+        assert_eq!(order, [2, 0, 1]);
+    }
+
+    #[test]
+    fn reconnects_while_the_lease_is_recent() {
+        let reconnect = Reconnect {
+            ssid: String::try_from("Office").unwrap(),
+            access_point: Some(ap(3)),
+            lease: Ipv4Settings {
+                address: [192, 168, 1, 40],
+                prefix_len: 24,
+                gateway: Some([192, 168, 1, 1]),
+                dns_servers: Vec::from_slice(&[[192, 168, 1, 1]]).unwrap(),
+            },
+            leased_at: 1_000_000,
+        };
+
+        assert_eq!(reconnect.usable(&NETWORKS, Some(1_000_000)), Some(1));
+        assert_eq!(
+            reconnect.usable(&NETWORKS, Some(1_000_000 + MAX_LEASE_AGE_SECONDS - 1)),
+            Some(1)
+        );
+        assert_eq!(
+            reconnect.usable(&NETWORKS, Some(1_000_000 + MAX_LEASE_AGE_SECONDS)),
+            None
+        );
+        // A clock that went backwards, or isn't set
+        assert_eq!(reconnect.usable(&NETWORKS, Some(999_999)), None);
+        assert_eq!(reconnect.usable(&NETWORKS, None), None);
+        // The network was removed since
+        assert_eq!(reconnect.usable(&NETWORKS[..1], Some(1_000_000)), None);
+    }
+
+    #[test]
+    fn parses_static_addresses() {
+        assert_eq!(
+            Ipv4Settings::parse("192.168.1.50/24, 192.168.1.1, 1.1.1.1,8.8.8.8"),
+            Some(Ipv4Settings {
+                address: [192, 168, 1, 50],
+                prefix_len: 24,
+                gateway: Some([192, 168, 1, 1]),
+                dns_servers: Vec::from_slice(&[[1, 1, 1, 1], [8, 8, 8, 8]]).unwrap(),
+            })
+        );
+
+        let no_gateway = Ipv4Settings::parse("10.0.0.9/8,,10.0.0.53").unwrap();
+        assert_eq!(no_gateway.gateway, None);
+        assert_eq!(no_gateway.dns_servers, [[10, 0, 0, 53]]);
+
+        for bad in [
+            "192.168.1.50",
+            "192.168.1.50/33",
+            "192.168.1/24",
+            "192.168.1.256/24",
+            "192.168.1.50.1/24",
+            "10.0.0.9/8,gateway",
+            "10.0.0.9/8,,1.1.1.1,1.0.0.1,8.8.8.8,8.8.4.4",
+        ] {
+            assert_eq!(Ipv4Settings::parse(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn pairs_static_addresses_with_ssids() {
+        let parsed = parse_static_ipv4("Office=10.1.2.3/16,10.1.0.1; Lab=bogus;;Phone");
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].0, "Office");
+        assert_eq!(parsed[0].1.as_ref().unwrap().address, [10, 1, 2, 3]);
+        assert_eq!(parsed[1], ("Lab", None));
+        assert!(parse_static_ipv4("").is_empty());
+    }
+}
@@ -0,0 +1,559 @@
+//! Golden image tests for the badge layout.
+//!
+//! Each case is rendered into a `Framebuffer` and compared against the PBM
+//! reference in `tests/snapshots`. A missing or different reference fails
+//! the test, and the rendered frame is written next to it as
+//! `<name>.actual.pbm`. Set `UPDATE_SNAPSHOTS=1` to record the references
+//! after an intended layout change.
+
+use std::fs;
+use std::path::PathBuf;
+
+use badger_core::{
+    air::{AirQuality, DEFAULT_AQI_WARNING},
+    battery::BatteryState,
+    framebuffer::Framebuffer,
+    http::HttpError,
+    image::IMAGES,
+    layout::{self, Badge, HEIGHT, Page, Screen, WIDTH},
+    sntp::SntpError,
+    sync::SyncError,
+    units::Units,
+    weather::{CurrentWeather, DailyForecast, FORECAST_HOURS, Forecast, Freshness, HourlyForecast},
+};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle};
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+const SYNC_ERRORS: [(&str, SyncError); 6] = [
+    ("join", SyncError::Join),
+    ("timeout", SyncError::Timeout),
+    ("ntp", SyncError::Time(SntpError::Transport)),
+    ("http_429", SyncError::Weather(HttpError::Status(429))),
+    ("parse", SyncError::Weather(HttpError::Parse)),
+    ("air_quality", SyncError::AirQuality(HttpError::Timeout)),
+];
+
+const WEATHER_CODES: [u8; 29] = [
+    0, 1, 2, 3, 45, 48, 51, 53, 55, 56, 57, 61, 63, 65, 66, 67, 71, 73, 75, 77, 80, 81, 82, 85, 86,
+    95, 96, 99, 100,
+];
+
+fn weather(code: u8) -> CurrentWeather {
+    CurrentWeather {
+        temperature: 27.4,
+        weathercode: code,
+        relative_humidity_2m: 74.0,
+        is_day: 1,
+        apparent_temperature: Some(30.9),
+        wind_speed_10m: Some(18.4),
+        wind_direction_10m: Some(110.0),
+        precipitation: Some(0.4),
+        uv_index: Some(8.2),
+    }
+}
+
+fn forecast() -> Forecast {
+    [
+        (30.1, 24.0, 2, Some(10)),
+        (-2.5, -11.0, 73, Some(100)),
+        (28.0, 22.9, 95, None),
+        (31.2, 24.4, 0, Some(0)),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(
+        |(i, (high, low, weathercode, precipitation_probability))| DailyForecast {
+            weekday: (3 + i as u8) % 7,
+            high,
+            low,
+            weathercode,
+            precipitation_probability,
+        },
+    )
+    .collect()
+}
+
+fn hourly() -> HourlyForecast {
+    HourlyForecast {
+        start_hour: 22,
+        temperature: (0..FORECAST_HOURS)
+            .map(|i| 18.0 + 8.0 * ((i as f32 - 4.0) / 24.0 * std::f32::consts::TAU).sin())
+            .collect(),
+        precipitation_probability: (0..FORECAST_HOURS as u8)
+            .map(|i| if (8..16).contains(&i) { i * 6 } else { 0 })
+            .collect(),
+    }
+}
+
+fn air_quality(aqi: f32) -> AirQuality {
+    AirQuality {
+        us_aqi: Some(aqi),
+        pm2_5: Some(14.3),
+        alder_pollen: Some(3.0),
+        birch_pollen: Some(120.0),
+        grass_pollen: Some(0.0),
+        ragweed_pollen: None,
+    }
+}
+
+fn at(hour: u8, minute: u8) -> PrimitiveDateTime {
+    let date = Date::from_calendar_date(2026, Month::January, 1).unwrap();
+    PrimitiveDateTime::new(date, Time::from_hms(hour, minute, 0).unwrap())
+}
+
+fn badge() -> Badge<'static> {
+    Badge {
+        weather: Some(weather(2)),
+        weather_freshness: Freshness::Fresh,
+        forecast: Some(forecast()),
+        hourly: Some(hourly()),
+        air_quality: Some(air_quality(42.0)),
+        aqi_warning: DEFAULT_AQI_WARNING,
+        time: Some(at(12, 34)),
+        power: Some(BatteryState::Battery(80)),
+        sync_error: None,
+        image: IMAGES[0],
+        page: Page::Image,
+        units: Units::Metric,
+    }
+}
+
+fn render(screen: Screen, badge: &Badge) -> Framebuffer {
+    let mut frame = Framebuffer::new();
+    let Ok(_) = layout::draw_screen(&mut frame, screen, badge);
+    frame
+}
+
+fn to_pbm(frame: &Framebuffer) -> Vec<u8> {
+    let mut pbm = format!("P4\n{WIDTH} {HEIGHT}\n").into_bytes();
+    pbm.extend_from_slice(frame.as_bytes());
+    pbm
+}
+
+/// Compares `frame` with the stored reference, returning a description of
+/// the difference if it doesn't match or there is no reference. With
+/// `UPDATE_SNAPSHOTS=1` the reference is (re)written instead.
+fn check_snapshot(name: &str, frame: &Framebuffer) -> Result<(), String> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let reference = dir.join(format!("{name}.pbm"));
+    let actual = dir.join(format!("{name}.actual.pbm"));
+    let rendered = to_pbm(frame);
+
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some_and(|v| v == "1");
+
+    match fs::read(&reference) {
+        Ok(expected) if expected == rendered => {
+            fs::remove_file(&actual).ok();
+            Ok(())
+        }
+        _ if update => {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&reference, &rendered).unwrap();
+            fs::remove_file(&actual).ok();
+            eprintln!("recorded snapshot {}", reference.display());
+            Ok(())
+        }
+        Err(_) => {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&actual, &rendered).unwrap();
+
+            Err(format!(
+                "{name}: no reference, see {} and rerun with UPDATE_SNAPSHOTS=1 to record it",
+                actual.display()
+            ))
+        }
+        Ok(expected) => {
+            fs::write(&actual, &rendered).unwrap();
+
+            let header = rendered.len() - frame.as_bytes().len();
+            let changed = expected
+                .get(header..)
+                .unwrap_or_default()
+                .iter()
+                .zip(frame.as_bytes())
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum::<u32>();
+
+            Err(format!(
+                "{name}: {changed} pixels differ, see {}",
+                actual.display()
+            ))
+        }
+    }
+}
+
+fn assert_snapshots(cases: impl IntoIterator<Item = (String, Framebuffer)>) {
+    let failures: Vec<String> = cases
+        .into_iter()
+        .filter_map(|(name, frame)| check_snapshot(&name, &frame).err())
+        .collect();
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+/// Bounding box of all black pixels in the frame
+fn ink_bounds(frame: &Framebuffer) -> Option<Rectangle> {
+    let points = frame
+        .bounding_box()
+        .points()
+        .filter(|p| frame.get_pixel(*p) == Some(BinaryColor::Off));
+
+    let (mut min, mut max) = (None::<Point>, None::<Point>);
+    for p in points {
+        min = Some(min.map_or(p, |m| m.component_min(p)));
+        max = Some(max.map_or(p, |m| m.component_max(p)));
+    }
+
+    Some(Rectangle::with_corners(min?, max?))
+}
+
+#[test]
+fn no_weather() {
+    let badge = Badge {
+        weather: None,
+        ..badge()
+    };
+
+    assert_snapshots([("no_weather".into(), render(Screen::TopBar, &badge))]);
+}
+
+#[test]
+fn weather_codes() {
+    assert_snapshots(WEATHER_CODES.map(|code| {
+        let badge = Badge {
+            weather: Some(weather(code)),
+            ..badge()
+        };
+
+        (
+            format!("weather_code_{code}"),
+            render(Screen::TopBar, &badge),
+        )
+    }));
+}
+
+#[test]
+fn sync_errors() {
+    assert_snapshots(SYNC_ERRORS.map(|(name, error)| {
+        let badge = Badge {
+            sync_error: Some(error),
+            ..badge()
+        };
+
+        (format!("sync_error_{name}"), render(Screen::TopBar, &badge))
+    }));
+}
+
+#[test]
+fn battery_states() {
+    let states = [
+        ("none", None),
+        ("error", Some(BatteryState::Error)),
+        ("usb", Some(BatteryState::UsbPower)),
+        ("5", Some(BatteryState::Battery(5))),
+        ("20", Some(BatteryState::Battery(20))),
+        ("40", Some(BatteryState::Battery(40))),
+        ("60", Some(BatteryState::Battery(60))),
+        ("80", Some(BatteryState::Battery(80))),
+        ("100", Some(BatteryState::Battery(100))),
+    ];
+
+    assert_snapshots(states.map(|(name, power)| {
+        let badge = Badge { power, ..badge() };
+
+        (format!("battery_{name}"), render(Screen::TopBar, &badge))
+    }));
+}
+
+#[test]
+fn times() {
+    let times = [
+        ("unset", None),
+        ("midnight", Some(at(0, 0))),
+        ("noon", Some(at(12, 0))),
+        ("before_midnight", Some(at(23, 59))),
+    ];
+
+    assert_snapshots(times.map(|(name, time)| {
+        let badge = Badge { time, ..badge() };
+
+        (format!("time_{name}"), render(Screen::TopBar, &badge))
+    }));
+}
+
+#[test]
+fn images() {
+    assert_snapshots(IMAGES.iter().enumerate().map(|(i, &image)| {
+        let badge = Badge { image, ..badge() };
+
+        (format!("image_{i}"), render(Screen::Full, &badge))
+    }));
+}
+
+#[test]
+fn forecast_page() {
+    let three_days = Badge {
+        forecast: Some(forecast().into_iter().take(3).collect()),
+        ..badge()
+    };
+    let empty = Badge {
+        forecast: None,
+        ..badge()
+    };
+    let full = Badge {
+        page: Page::Forecast,
+        ..badge()
+    };
+
+    assert_snapshots([
+        ("forecast".into(), render(Screen::Forecast, &badge())),
+        (
+            "forecast_3_days".into(),
+            render(Screen::Forecast, &three_days),
+        ),
+        ("forecast_empty".into(), render(Screen::Forecast, &empty)),
+        ("forecast_full".into(), render(Screen::Full, &full)),
+    ]);
+}
+
+#[test]
+fn hourly_page() {
+    let flat = Badge {
+        hourly: Some(HourlyForecast {
+            start_hour: 0,
+            temperature: [-4.0; FORECAST_HOURS].into_iter().collect(),
+            precipitation_probability: [100; FORECAST_HOURS].into_iter().collect(),
+        }),
+        ..badge()
+    };
+    let empty = Badge {
+        hourly: None,
+        ..badge()
+    };
+
+    assert_snapshots([
+        ("hourly".into(), render(Screen::Hourly, &badge())),
+        ("hourly_flat".into(), render(Screen::Hourly, &flat)),
+        ("hourly_empty".into(), render(Screen::Hourly, &empty)),
+    ]);
+}
+
+#[test]
+fn details_page() {
+    let sparse = Badge {
+        weather: Some(CurrentWeather {
+            wind_speed_10m: None,
+            wind_direction_10m: None,
+            uv_index: None,
+            ..weather(61)
+        }),
+        ..badge()
+    };
+    let empty = Badge {
+        weather: None,
+        ..badge()
+    };
+
+    assert_snapshots([
+        ("details".into(), render(Screen::Details, &badge())),
+        ("details_sparse".into(), render(Screen::Details, &sparse)),
+        ("details_empty".into(), render(Screen::Details, &empty)),
+    ]);
+}
+
+#[test]
+fn air_quality_page() {
+    let sparse = Badge {
+        air_quality: Some(AirQuality {
+            us_aqi: Some(163.0),
+            pm2_5: None,
+            ..Default::default()
+        }),
+        ..badge()
+    };
+    let empty = Badge {
+        air_quality: None,
+        ..badge()
+    };
+    let full = Badge {
+        page: Page::AirQuality,
+        ..badge()
+    };
+    let failed = Badge {
+        sync_error: Some(SyncError::AirQuality(HttpError::Timeout)),
+        ..full.clone()
+    };
+    let failed_empty = Badge {
+        air_quality: None,
+        ..failed.clone()
+    };
+
+    assert_snapshots([
+        ("air_quality".into(), render(Screen::AirQuality, &badge())),
+        (
+            "air_quality_sparse".into(),
+            render(Screen::AirQuality, &sparse),
+        ),
+        (
+            "air_quality_empty".into(),
+            render(Screen::AirQuality, &empty),
+        ),
+        ("air_quality_full".into(), render(Screen::Full, &full)),
+        ("air_quality_failed".into(), render(Screen::Full, &failed)),
+        (
+            "air_quality_failed_empty".into(),
+            render(Screen::AirQuality, &failed_empty),
+        ),
+    ]);
+}
+
+#[test]
+fn aqi_warning_in_top_bar() {
+    let unhealthy = Badge {
+        air_quality: Some(air_quality(163.0)),
+        ..badge()
+    };
+    let raised_threshold = Badge {
+        aqi_warning: 200,
+        ..unhealthy.clone()
+    };
+    let sync_failed = Badge {
+        sync_error: Some(SyncError::Join),
+        ..unhealthy.clone()
+    };
+
+    // Below the threshold the top bar is the same as without air quality
+    let no_air_quality = Badge {
+        air_quality: None,
+        ..badge()
+    };
+    assert_eq!(
+        render(Screen::TopBar, &raised_threshold).as_bytes(),
+        render(Screen::TopBar, &no_air_quality).as_bytes()
+    );
+
+    assert_snapshots([
+        ("aqi_warning".into(), render(Screen::TopBar, &unhealthy)),
+        (
+            "aqi_warning_sync_error".into(),
+            render(Screen::TopBar, &sync_failed),
+        ),
+    ]);
+}
+
+#[test]
+fn provisioning() {
+    assert_snapshots([(
+        "provisioning".into(),
+        render(Screen::Provisioning, &badge()),
+    )]);
+}
+
+#[test]
+fn stale_weather() {
+    let stale = |freshness| Badge {
+        weather_freshness: freshness,
+        ..badge()
+    };
+    let no_weather = Badge {
+        weather: None,
+        ..badge()
+    };
+
+    // Past the hard limit the weather goes, on the details page too
+    for screen in [Screen::TopBar, Screen::Details] {
+        assert_eq!(
+            render(screen, &stale(Freshness::Expired)).as_bytes(),
+            render(screen, &no_weather).as_bytes()
+        );
+    }
+
+    assert_snapshots([
+        (
+            "stale_hours".into(),
+            render(Screen::TopBar, &stale(Freshness::Stale(5))),
+        ),
+        (
+            "stale_days".into(),
+            render(Screen::TopBar, &stale(Freshness::Stale(50))),
+        ),
+    ]);
+}
+
+#[test]
+fn imperial_units() {
+    let badge = Badge {
+        weather: Some(CurrentWeather {
+            temperature: 81.3,
+            ..weather(2)
+        }),
+        units: Units::Imperial,
+        ..badge()
+    };
+
+    assert_snapshots([
+        ("imperial_top_bar".into(), render(Screen::TopBar, &badge)),
+        ("imperial_forecast".into(), render(Screen::Forecast, &badge)),
+        ("imperial_hourly".into(), render(Screen::Hourly, &badge)),
+        ("imperial_details".into(), render(Screen::Details, &badge)),
+    ]);
+}
+
+#[test]
+fn weather_text_stays_clear_of_clock() {
+    let mut clock = Framebuffer::new();
+    let Ok(_) = layout::draw_time(&mut clock, Some(at(12, 59)), Some(BatteryState::UsbPower));
+    let clock = ink_bounds(&clock).unwrap();
+
+    for code in WEATHER_CODES {
+        let mut frame = Framebuffer::new();
+        let Ok(_) = layout::draw_weather(
+            &mut frame,
+            Some(&weather(code)),
+            Freshness::Fresh,
+            None,
+            None,
+            Units::Metric,
+        );
+        let text = ink_bounds(&frame).unwrap();
+
+        assert!(
+            text.bottom_right().unwrap().x < clock.top_left.x,
+            "weather code {code} runs into the clock: {text:?} vs {clock:?}"
+        );
+    }
+
+    for (_, error) in SYNC_ERRORS {
+        let mut frame = Framebuffer::new();
+        let Ok(_) = layout::draw_weather(
+            &mut frame,
+            Some(&weather(0)),
+            Freshness::Fresh,
+            Some(&error),
+            None,
+            Units::Metric,
+        );
+        let text = ink_bounds(&frame).unwrap();
+
+        assert!(
+            text.bottom_right().unwrap().x < clock.top_left.x,
+            "{error:?} runs into the clock: {text:?} vs {clock:?}"
+        );
+    }
+
+    let mut frame = Framebuffer::new();
+    let Ok(_) = layout::draw_weather(
+        &mut frame,
+        Some(&weather(0)),
+        Freshness::Fresh,
+        None,
+        Some(500),
+        Units::Metric,
+    );
+    let text = ink_bounds(&frame).unwrap();
+
+    assert!(
+        text.bottom_right().unwrap().x < clock.top_left.x,
+        "AQI warning runs into the clock: {text:?} vs {clock:?}"
+    );
+}
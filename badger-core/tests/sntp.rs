@@ -0,0 +1,144 @@
+//! Runs the SNTP client against stand-in servers on localhost.
+
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use badger_core::sntp::{self, NTP_PORT, PACKET_SIZE, SntpError, Transport};
+use embassy_futures::block_on;
+
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Sends every request to a fixed local port instead of `server:123`
+struct LocalUdp {
+    socket: UdpSocket,
+    port: u16,
+}
+
+impl LocalUdp {
+    fn new(port: u16) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+
+        Self { socket, port }
+    }
+}
+
+impl Transport for LocalUdp {
+    async fn exchange(
+        &mut self,
+        _server: &str,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, SntpError> {
+        self.socket
+            .send_to(request, ("127.0.0.1", self.port))
+            .map_err(|_| SntpError::Transport)?;
+
+        self.socket.recv(response).map_err(|_| SntpError::Transport)
+    }
+}
+
+/// Answers `count` requests, letting `tweak` corrupt each reply
+fn stand_in_server(count: usize, tweak: fn(&mut [u8; PACKET_SIZE])) -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for _ in 0..count {
+            let mut request = [0u8; PACKET_SIZE];
+            let Ok((_, peer)) = socket.recv_from(&mut request) else {
+                return;
+            };
+
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let seconds = now.as_secs() + NTP_UNIX_OFFSET;
+            let fraction = ((now.subsec_nanos() as u64) << 32) / 1_000_000_000;
+            let timestamp = ((seconds << 32) | fraction).to_be_bytes();
+
+            let mut reply = [0u8; PACKET_SIZE];
+            reply[0] = (4 << 3) | 4;
+            reply[1] = 2;
+            reply[24..32].copy_from_slice(&request[40..48]);
+            reply[32..40].copy_from_slice(&timestamp);
+            reply[40..48].copy_from_slice(&timestamp);
+
+            tweak(&mut reply);
+            socket.send_to(&reply, peer).unwrap();
+        }
+    });
+
+    port
+}
+
+fn query(transport: &mut LocalUdp, servers: &[&str]) -> Result<time::PrimitiveDateTime, SntpError> {
+    let start = Instant::now();
+    let mut nonce = 0x1234_5678_u64;
+
+    block_on(sntp::query(
+        transport,
+        servers,
+        || {
+            nonce += 1;
+            nonce
+        },
+        || start.elapsed().as_micros() as u64,
+        1_000_000,
+    ))
+}
+
+#[test]
+fn gets_time_from_server() {
+    let port = stand_in_server(1, |_| {});
+    let now = query(&mut LocalUdp::new(port), &["pool.ntp.org"]).unwrap();
+
+    let unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let expected = time::OffsetDateTime::from_unix_timestamp(unix as i64).unwrap();
+    let drift = (now.assume_utc() - expected).abs();
+
+    assert!(drift < time::Duration::seconds(2), "drift {drift}");
+    assert_eq!(NTP_PORT, 123);
+}
+
+#[test]
+fn falls_through_to_next_server() {
+    // First reply claims an unsynchronised clock, second is fine
+    let port = stand_in_server(2, |reply| {
+        static FIRST: std::sync::Once = std::sync::Once::new();
+        FIRST.call_once(|| reply[0] |= 3 << 6);
+    });
+
+    let mut transport = LocalUdp::new(port);
+    assert!(query(&mut transport, &["a.example", "b.example"]).is_ok());
+}
+
+#[test]
+fn reports_last_error() {
+    let port = stand_in_server(2, |reply| reply[1] = 0);
+
+    assert_eq!(
+        query(&mut LocalUdp::new(port), &["a.example", "b.example"]),
+        Err(SntpError::KissOfDeath)
+    );
+}
+
+#[test]
+fn times_out_without_server() {
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = silent.local_addr().unwrap().port();
+
+    assert_eq!(
+        query(&mut LocalUdp::new(port), &["pool.ntp.org"]),
+        Err(SntpError::Transport)
+    );
+}
+
+#[test]
+fn no_servers() {
+    assert_eq!(query(&mut LocalUdp::new(1), &[]), Err(SntpError::NoServers));
+}
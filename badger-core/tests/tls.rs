@@ -0,0 +1,206 @@
+//! Checks the certificate pin against a local HTTPS server with a
+//! self-signed certificate, run with the `openssl` command line tool the
+//! README uses to compute pins.
+
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use badger_core::tls::{Pin, PinnedProvider, Trust, p256_public_key, public_key_info};
+use embedded_tls::blocking::TlsConnection;
+use embedded_tls::{Aes128GcmSha256, TlsConfig, TlsContext, TlsError};
+use rand_core::OsRng;
+
+/// Pin of `tests/tls/localhost.pem`, from the command in the README
+const LOCALHOST_PIN: &str = "pin:dab41729987b5e42e93be89339c1fd346dc8a476530d30d817c8d055eb5a40cd";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/tls")
+        .join(name)
+}
+
+fn openssl(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new("openssl")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("these tests need the openssl command");
+
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap().stdout
+}
+
+/// `openssl s_server` serving `name`.pem the way the badge talks to it,
+/// killed when dropped
+struct Server {
+    child: Child,
+    port: u16,
+}
+
+impl Server {
+    fn start(name: &str) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let cert = fixture(&format!("{name}.pem"));
+        let key = fixture(&format!("{name}.key"));
+
+        let child = Command::new("openssl")
+            .args(["s_server", "-quiet", "-www", "-tls1_3"])
+            .args(["-ciphersuites", "TLS_AES_128_GCM_SHA256"])
+            .args(["-accept", &format!("127.0.0.1:{port}")])
+            .arg("-cert")
+            .arg(cert)
+            .arg("-key")
+            .arg(key)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("these tests need the openssl command");
+
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "openssl s_server didn't start"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        Self { child, port }
+    }
+
+    /// Opens a connection the way the badge does with `pin`, then fetches
+    /// the status page `-www` serves
+    fn get(&self, pin: &Pin) -> Result<String, TlsError> {
+        let socket = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        let mut read_buffer = [0; 16640];
+        let mut write_buffer = [0; 4096];
+
+        let mut connection = TlsConnection::<_, Aes128GcmSha256>::new(
+            Socket(socket),
+            &mut read_buffer,
+            &mut write_buffer,
+        );
+        let config = TlsConfig::new().with_server_name("localhost");
+        let mut provider = PinnedProvider::new(OsRng, pin);
+
+        connection.open(TlsContext::new(&config, &mut provider))?;
+        assert!(provider.verified());
+
+        connection.write(b"GET / HTTP/1.0\r\n\r\n")?;
+        connection.flush()?;
+
+        let mut response = Vec::new();
+        let mut chunk = [0; 1024];
+        loop {
+            match connection.read(&mut chunk) {
+                Ok(0) | Err(TlsError::ConnectionClosed) => break,
+                Ok(len) => response.extend_from_slice(&chunk[..len]),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&response).into_owned())
+    }
+
+    /// The certificate the server presents in a TLS 1.3 handshake, as DER
+    fn certificate(&self) -> Vec<u8> {
+        let connect = format!("127.0.0.1:{}", self.port);
+        let pem = openssl(&["s_client", "-connect", &connect, "-tls1_3"], b"");
+
+        openssl(&["x509", "-outform", "der"], &pem)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// embedded-tls reads and writes through embedded-io
+struct Socket(TcpStream);
+
+impl embedded_io::ErrorType for Socket {
+    type Error = io::Error;
+}
+
+impl embedded_io::Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        io::Read::read(&mut self.0, buf)
+    }
+}
+
+impl embedded_io::Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        io::Write::write(&mut self.0, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        io::Write::flush(&mut self.0)
+    }
+}
+
+fn sha256(data: &[u8]) -> Pin {
+    openssl(&["dgst", "-sha256", "-binary"], data)
+        .try_into()
+        .unwrap()
+}
+
+fn pin(trust: Option<Trust>) -> Pin {
+    match trust {
+        Some(Trust::Pin(pin)) => pin,
+        trust => panic!("not a pin: {trust:?}"),
+    }
+}
+
+#[test]
+fn pins_the_served_certificate() {
+    let server = Server::start("localhost");
+    let certificate = server.certificate();
+
+    let public_key_info = public_key_info(&certificate).unwrap();
+
+    assert_eq!(sha256(public_key_info), pin(Trust::parse(LOCALHOST_PIN)));
+    assert_eq!(p256_public_key(public_key_info).map(<[u8]>::len), Some(65));
+}
+
+#[test]
+fn other_certificate_does_not_match() {
+    let server = Server::start("other");
+    let certificate = server.certificate();
+
+    let public_key_info = public_key_info(&certificate).unwrap();
+
+    assert_ne!(sha256(public_key_info), pin(Trust::parse(LOCALHOST_PIN)));
+}
+
+#[test]
+fn handshake_accepts_the_pinned_key() {
+    let server = Server::start("localhost");
+
+    let response = server.get(&pin(Trust::parse(LOCALHOST_PIN))).unwrap();
+
+    assert!(response.starts_with("HTTP/1.0 200 ok"), "{response}");
+}
+
+#[test]
+fn handshake_refuses_another_key() {
+    let server = Server::start("other");
+
+    let error = server.get(&pin(Trust::parse(LOCALHOST_PIN))).unwrap_err();
+
+    assert!(matches!(error, TlsError::InvalidCertificate), "{error:?}");
+}
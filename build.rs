@@ -30,6 +30,20 @@ fn setup_env() {
     }
 }
 
+/// The WiFi passwords are only a first boot default, see `wifi.rs`, so a
+/// missing `.wifi` builds firmware without any
+fn setup_wifi() {
+    println!("cargo:rerun-if-changed=.wifi");
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let passwords = std::fs::read(".wifi").unwrap_or_default();
+
+    File::create(out.join("wifi"))
+        .unwrap()
+        .write_all(&passwords)
+        .unwrap();
+}
+
 fn setup_build() {
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
@@ -54,5 +68,6 @@ fn setup_build() {
 
 fn main() {
     setup_env();
+    setup_wifi();
     setup_build();
 }
@@ -0,0 +1,314 @@
+//! Renders every `Screen` of the badge layout to image files on the host, so
+//! layouts can be checked without flashing the device.
+//!
+//! ```text
+//! cargo run -- [--out DIR] [--format png|pbm] [--weather TEMP,CODE,HUMIDITY[,IS_DAY] | --no-weather]
+//!              [--time HH:MM | --no-time] [--power usb|error|none|PERCENT] [--image INDEX]
+//!              [--no-forecast] [--units metric|imperial] [--aqi AQI | --no-air-quality]
+//!              [--weather-age HOURS]
+//! ```
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use badger_core::{
+    air::{AirQuality, DEFAULT_AQI_WARNING},
+    battery::BatteryState,
+    framebuffer::Framebuffer,
+    image::IMAGES,
+    layout::{self, Badge, HEIGHT, Page, Screen, WIDTH},
+    units::Units,
+    weather::{CurrentWeather, DailyForecast, FORECAST_HOURS, Forecast, HourlyForecast, MaxAge},
+};
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+enum Format {
+    Png,
+    Pbm,
+}
+
+struct Options {
+    out: PathBuf,
+    format: Format,
+    weather: Option<CurrentWeather>,
+    /// Hours since the weather was fetched
+    weather_age: i64,
+    forecast: Option<Forecast>,
+    hourly: Option<HourlyForecast>,
+    air_quality: Option<AirQuality>,
+    time: Option<PrimitiveDateTime>,
+    power: Option<BatteryState>,
+    image: usize,
+    units: Units,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            out: PathBuf::from("sim-out"),
+            format: Format::Png,
+            weather: Some(CurrentWeather {
+                temperature: 27.4,
+                weathercode: 2,
+                relative_humidity_2m: 74.0,
+                is_day: 1,
+                apparent_temperature: Some(30.9),
+                wind_speed_10m: Some(18.4),
+                wind_direction_10m: Some(110.0),
+                precipitation: Some(0.4),
+                uv_index: Some(8.2),
+            }),
+            weather_age: 0,
+            forecast: Some(sample_forecast()),
+            hourly: Some(sample_hourly()),
+            air_quality: Some(AirQuality {
+                us_aqi: Some(57.0),
+                pm2_5: Some(14.3),
+                alder_pollen: Some(3.0),
+                birch_pollen: Some(120.0),
+                grass_pollen: Some(0.0),
+                ragweed_pollen: None,
+            }),
+            time: Some(at(12, 34)),
+            power: Some(BatteryState::Battery(80)),
+            image: 0,
+            units: Units::Metric,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match render_all(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to write output: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn render_all(options: &Options) -> std::io::Result<()> {
+    fs::create_dir_all(&options.out)?;
+
+    let badge = Badge {
+        weather: options.weather,
+        weather_freshness: MaxAge::default().for_age(options.weather_age),
+        forecast: options.forecast.clone(),
+        hourly: options.hourly.clone(),
+        air_quality: options.air_quality,
+        aqi_warning: DEFAULT_AQI_WARNING,
+        time: options.time,
+        power: options.power,
+        sync_error: None,
+        image: IMAGES[options.image],
+        page: Page::Image,
+        units: options.units,
+    };
+
+    for screen in Screen::ALL {
+        let mut frame = Framebuffer::new();
+        let Ok(_) = layout::draw_screen(&mut frame, screen, &badge);
+
+        let name = format!("{screen:?}").to_lowercase();
+        let path = match options.format {
+            Format::Png => options.out.join(format!("{name}.png")),
+            Format::Pbm => options.out.join(format!("{name}.pbm")),
+        };
+
+        match options.format {
+            Format::Png => write_png(&path, &frame)?,
+            Format::Pbm => write_pbm(&path, &frame)?,
+        }
+
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn write_pbm(path: &Path, frame: &Framebuffer) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    write!(file, "P4\n{WIDTH} {HEIGHT}\n")?;
+    file.write_all(frame.as_bytes())?;
+    file.flush()
+}
+
+fn write_png(path: &Path, frame: &Framebuffer) -> std::io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+
+    // PNG grayscale treats a set bit as white, the framebuffer as black
+    let data: Vec<u8> = frame.as_bytes().iter().map(|b| !b).collect();
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+
+        match arg.as_str() {
+            "--out" => options.out = PathBuf::from(value()?),
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "png" => Format::Png,
+                    "pbm" => Format::Pbm,
+                    other => return Err(format!("Unknown format: {other}")),
+                }
+            }
+            "--weather" => options.weather = Some(parse_weather(&value()?)?),
+            "--no-weather" => options.weather = None,
+            "--weather-age" => {
+                let hours = value()?;
+                options.weather_age = hours
+                    .parse()
+                    .map_err(|_| format!("Expected hours for --weather-age, got {hours}"))?;
+            }
+            "--no-forecast" => {
+                options.forecast = None;
+                options.hourly = None;
+            }
+            "--aqi" => {
+                let aqi = value()?;
+                let aqi = aqi
+                    .parse::<f32>()
+                    .map_err(|_| format!("Expected a number for --aqi, got {aqi}"))?;
+                let air_quality = options.air_quality.get_or_insert_with(Default::default);
+                air_quality.us_aqi = Some(aqi);
+            }
+            "--no-air-quality" => options.air_quality = None,
+            "--time" => options.time = Some(parse_time(&value()?)?),
+            "--no-time" => options.time = None,
+            "--power" => options.power = parse_power(&value()?)?,
+            "--image" => {
+                let index = value()?;
+                options.image = index
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|i| *i < IMAGES.len())
+                    .ok_or_else(|| format!("Image index must be below {}", IMAGES.len()))?;
+            }
+            "--units" => {
+                let units = value()?;
+                options.units =
+                    Units::parse(&units).ok_or_else(|| format!("Unknown units: {units}"))?;
+            }
+            other => return Err(format!("Unknown argument: {other}")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_weather(value: &str) -> Result<CurrentWeather, String> {
+    let invalid = || format!("Expected TEMP,CODE,HUMIDITY[,IS_DAY], got {value}");
+
+    let mut parts = value.split(',');
+    let mut next = || parts.next().map(str::trim).ok_or_else(invalid);
+
+    let temperature = next()?.parse().map_err(|_| invalid())?;
+    let weathercode = next()?.parse().map_err(|_| invalid())?;
+    let relative_humidity_2m = next()?.parse().map_err(|_| invalid())?;
+    let is_day = match parts.next() {
+        Some(is_day) => is_day.trim().parse().map_err(|_| invalid())?,
+        None => 1,
+    };
+
+    Ok(CurrentWeather {
+        temperature,
+        weathercode,
+        relative_humidity_2m,
+        is_day,
+        ..Default::default()
+    })
+}
+
+fn parse_time(value: &str) -> Result<PrimitiveDateTime, String> {
+    let invalid = || format!("Expected HH:MM, got {value}");
+
+    let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+    let hour = hour.parse::<u8>().map_err(|_| invalid())?;
+    let minute = minute.parse::<u8>().map_err(|_| invalid())?;
+
+    if hour > 23 || minute > 59 {
+        return Err(invalid());
+    }
+
+    Ok(at(hour, minute))
+}
+
+fn parse_power(value: &str) -> Result<Option<BatteryState>, String> {
+    match value {
+        "none" => Ok(None),
+        "usb" => Ok(Some(BatteryState::UsbPower)),
+        "error" => Ok(Some(BatteryState::Error)),
+        x => x
+            .parse::<u8>()
+            .ok()
+            .filter(|p| *p <= 100)
+            .map(|p| Some(BatteryState::Battery(p)))
+            .ok_or_else(|| format!("Expected usb, error, none or 0-100, got {x}")),
+    }
+}
+
+fn sample_forecast() -> Forecast {
+    [
+        (30.1, 24.0, 2, Some(10)),
+        (29.5, 23.8, 61, Some(80)),
+        (28.0, 22.9, 95, None),
+        (31.2, 24.4, 0, Some(0)),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(
+        |(i, (high, low, weathercode, precipitation_probability))| DailyForecast {
+            weekday: (3 + i as u8) % 7,
+            high,
+            low,
+            weathercode,
+            precipitation_probability,
+        },
+    )
+    .collect()
+}
+
+fn sample_hourly() -> HourlyForecast {
+    HourlyForecast {
+        start_hour: 13,
+        temperature: (0..FORECAST_HOURS)
+            .map(|i| 24.0 + 5.0 * ((i as f32 + 3.0) / 24.0 * std::f32::consts::TAU).sin())
+            .collect(),
+        precipitation_probability: (0..FORECAST_HOURS as u8)
+            .map(|i| if (4..10).contains(&i) { 20 + i * 5 } else { 0 })
+            .collect(),
+    }
+}
+
+fn at(hour: u8, minute: u8) -> PrimitiveDateTime {
+    let date = Date::from_calendar_date(2026, Month::January, 1).unwrap();
+    let time = Time::from_hms(hour, minute, 0).unwrap();
+
+    PrimitiveDateTime::new(date, time)
+}
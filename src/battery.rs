@@ -1,3 +1,4 @@
+use badger_core::battery::{BatteryState, adc_to_voltage, get_battery_state};
 use embassy_rp::{
     adc::{self, Adc, Channel},
     gpio::{Level, Output, Pull},
@@ -6,27 +7,6 @@ use embassy_time::Timer;
 
 use crate::{Irqs, state::POWER_INFO};
 
-pub enum BatteryState {
-    Error,
-    UsbPower,
-    Battery(u8),
-}
-
-fn get_battery_state(voltage: f32) -> BatteryState {
-    match voltage {
-        x if x > 4.5 => BatteryState::UsbPower,
-        x => {
-            let max_v = 4.2;
-            let min_v = 3.1;
-
-            let actual = x.clamp(min_v, max_v);
-            let percentage = (actual - min_v) / (max_v - min_v);
-
-            BatteryState::Battery((percentage * 100.0) as u8)
-        }
-    }
-}
-
 pub async fn get_power_state() {
     let p = unsafe { embassy_rp::Peripherals::steal() };
 
@@ -50,7 +30,7 @@ pub async fn get_power_state() {
 
     val /= 10;
 
-    let ret = get_battery_state(((val as f32) / 4095.0) * 3.3 * 3.0);
+    let ret = get_battery_state(adc_to_voltage(val));
     *POWER_INFO.lock().await = Some(ret);
 
     wifi_switch.set_low();
@@ -1,10 +1,11 @@
+use badger_core::layout::{Page, Screen};
 use embassy_rp::gpio::Input;
 use embassy_time::Timer;
 
 use crate::{
     FlashDevice, UserLed, flash, image,
     led::blink,
-    state::{BUTTON_PRESSED, Button, DISPLAY_CHANGED, Screen, UPDATE_WEATHER},
+    state::{BUTTON_PRESSED, Button, CURRENT_PAGE, DISPLAY_CHANGED, UPDATE_WEATHER},
 };
 
 #[embassy_executor::task(pool_size = 5)]
@@ -33,10 +34,20 @@ pub async fn handle_presses(user_led: &'static UserLed, flash: &'static FlashDev
 
                 DISPLAY_CHANGED.signal(Screen::Full);
             }
-            Button::C => {}
+            Button::C => {
+                blink(user_led, 1).await;
+
+                let page = {
+                    let mut page = CURRENT_PAGE.lock().await;
+                    *page = page.next();
+                    *page
+                };
+                DISPLAY_CHANGED.signal(page.screen());
+            }
             Button::Down => {
                 blink(user_led, 1).await;
 
+                show_images().await;
                 image::next();
                 flash::save_state(flash).await;
                 DISPLAY_CHANGED.signal(Screen::Image);
@@ -44,6 +55,7 @@ pub async fn handle_presses(user_led: &'static UserLed, flash: &'static FlashDev
             Button::Up => {
                 blink(user_led, 1).await;
 
+                show_images().await;
                 image::prev();
                 flash::save_state(flash).await;
                 DISPLAY_CHANGED.signal(Screen::Image);
@@ -51,3 +63,8 @@ pub async fn handle_presses(user_led: &'static UserLed, flash: &'static FlashDev
         }
     }
 }
+
+/// Up and Down page through images, so bring them back from the forecasts
+async fn show_images() {
+    *CURRENT_PAGE.lock().await = Page::Image;
+}
@@ -1,12 +1,18 @@
 use core::sync::atomic::Ordering;
 
+use badger_core::storage::{
+    CREDENTIALS_SIZE, Credentials, POSTCARD_SIZE, Postcard, from_unix_time, to_unix_time,
+};
 use embassy_rp::flash::{Async, Flash};
 use embassy_rp::peripherals::FLASH;
 use embedded_storage_async::nor_flash::NorFlash;
-use serde::{Deserialize, Serialize};
 
 use crate::FlashDevice;
-use crate::state::{CURRENT_IMAGE, CurrentWeather, WEATHER};
+use crate::http::units;
+use crate::state::{
+    AIR_QUALITY, CURRENT_IMAGE, FORECAST, HOURLY, LAST_NETWORK, LAST_SYNC, RECONNECT, WEATHER,
+    WEATHER_FETCHED,
+};
 
 // The type signature for Async Flash (size is 2MB = 2097152)
 pub type FlashDriver = Flash<'static, FLASH, Async, 2097152>;
@@ -15,21 +21,36 @@ pub type FlashDriver = Flash<'static, FLASH, Async, 2097152>;
 const FLASH_OFFSET: u32 = 0x200000 - 0x1000; // Top of 2MB
 const FLASH_SIZE: u32 = 4096;
 
-#[derive(Serialize, Deserialize)]
-struct Postcard {
-    weather: Option<CurrentWeather>,
-    image: usize,
-}
+// WiFi credentials get the sector below, so saving the weather never risks them
+const CREDENTIALS_OFFSET: u32 = FLASH_OFFSET - FLASH_SIZE;
 
 pub async fn save_state(flash: &'static FlashDevice) {
     let image = CURRENT_IMAGE.load(Ordering::Relaxed);
     let weather = *WEATHER.lock().await;
+    let weather_fetched = WEATHER_FETCHED.lock().await.map(to_unix_time);
+    let last_sync = LAST_SYNC.lock().await.map(to_unix_time);
+    let last_network = LAST_NETWORK.lock().await.clone();
+    let reconnect = RECONNECT.lock().await.clone();
+    let forecast = FORECAST.lock().await.clone();
+    let hourly = HOURLY.lock().await.clone();
+    let air_quality = *AIR_QUALITY.lock().await;
 
-    let postcard = Postcard { weather, image };
+    let postcard = Postcard {
+        weather,
+        weather_fetched,
+        last_sync,
+        last_network,
+        reconnect,
+        image,
+        forecast,
+        hourly,
+        air_quality,
+        units: units(),
+    };
 
     // 1. Serialize to RAM
-    let mut buf = [0u8; 128];
-    let slice = match postcard::to_slice(&postcard, &mut buf) {
+    let mut buf = [0u8; POSTCARD_SIZE];
+    let slice = match postcard.to_slice(&mut buf) {
         Ok(s) => s,
         Err(_) => {
             defmt::error!("Serialization failed - buffer too small?");
@@ -45,7 +66,7 @@ pub async fn save_state(flash: &'static FlashDevice) {
 }
 
 pub async fn load_state(flash: &'static FlashDevice) {
-    let mut buf = [0u8; 128];
+    let mut buf = [0u8; POSTCARD_SIZE];
 
     // 1. Read (Async - uses DMA)
     if flash
@@ -59,9 +80,60 @@ pub async fn load_state(flash: &'static FlashDevice) {
     }
 
     // 2. Deserialize (Sync)
-    if let Ok(postcard) = postcard::from_bytes::<Postcard>(&buf) {
+    let Ok(postcard) = Postcard::from_bytes(&buf) else {
+        return;
+    };
+
+    *LAST_SYNC.lock().await = postcard.last_sync.and_then(from_unix_time);
+    *LAST_NETWORK.lock().await = postcard.last_network;
+    *RECONNECT.lock().await = postcard.reconnect;
+
+    // Weather cached before a change of units would be shown with the wrong
+    // symbols, so it waits for the next sync instead
+    if postcard.units == units() {
         let mut weather = WEATHER.lock().await;
         *weather = postcard.weather;
+        *WEATHER_FETCHED.lock().await = postcard.weather_fetched.and_then(from_unix_time);
+        *FORECAST.lock().await = postcard.forecast;
+        *HOURLY.lock().await = postcard.hourly;
+        *AIR_QUALITY.lock().await = postcard.air_quality;
         // CURRENT_IMAGE.store(postcard.image, core::sync::atomic::Ordering::Relaxed);
     }
 }
+
+pub async fn save_credentials(flash: &'static FlashDevice, credentials: &Credentials) {
+    let mut buf = [0u8; CREDENTIALS_SIZE];
+    let slice = match credentials.to_slice(&mut buf) {
+        Ok(s) => s,
+        Err(_) => {
+            defmt::error!("Credentials serialization failed");
+            return;
+        }
+    };
+
+    let mut flash = flash.lock().await;
+
+    let _ = flash
+        .erase(CREDENTIALS_OFFSET, CREDENTIALS_OFFSET + FLASH_SIZE)
+        .await;
+    let _ = flash.write(CREDENTIALS_OFFSET, slice).await;
+}
+
+/// Reads the WiFi credentials. On first boot, with nothing readable in
+/// flash, `default` is written there and used.
+pub async fn load_credentials(flash: &'static FlashDevice, default: Credentials) -> Credentials {
+    let mut buf = [0u8; CREDENTIALS_SIZE];
+
+    let read = flash.lock().await.read(CREDENTIALS_OFFSET, &mut buf).await;
+
+    if read.is_ok()
+        && let Ok(credentials) = Credentials::from_bytes(&buf)
+    {
+        return credentials;
+    }
+
+    defmt::info!("No WiFi credentials in flash, or an older version, storing the built-in ones");
+    save_credentials(flash, &default).await;
+
+    default
+}
@@ -1,151 +1,448 @@
-use defmt::{Format, error};
+use badger_core::air::{AirQuality, DEFAULT_AQI_WARNING};
+use badger_core::api::{
+    Location, OpenMeteoAirQualityResponse, OpenMeteoQuery, air_quality_url, met_norway_url,
+};
+use badger_core::http::{HttpError, MAX_REDIRECTS, Target, Url, check_response, parse_json};
+use badger_core::provider::{Provider, WeatherProvider};
+use badger_core::retry::{Backoff, Retry};
+#[cfg(feature = "tls")]
+use badger_core::tls::Trust;
+use badger_core::units::Units;
+use badger_core::weather::{CurrentWeather, DailyForecast, HourlyForecast, MaxAge};
+use core::net::SocketAddr;
+use defmt::{error, warn};
 use embassy_net::Stack;
 use embassy_net::dns::DnsSocket;
 use embassy_net::tcp::client::{TcpClient, TcpClientState};
-use heapless::Vec;
+use embassy_rp::clocks::RoscRng;
+#[cfg(feature = "tls")]
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_io_async::Read;
+use embedded_nal_async::{AddrType, Dns, TcpConnect};
 use log::info;
-use reqwless::client::HttpClient;
-use reqwless::request::{Method, RequestBuilder};
-use serde::Deserialize;
-use time::{Date, Month, PrimitiveDateTime, Time};
+use reqwless::client::HttpConnection;
+use reqwless::request::{Request, RequestBuilder};
 
-use crate::state::{CurrentWeather, POWER_MUTEX, WEATHER};
-use crate::time::set_time;
-use crate::{FlashDevice, RtcDevice, flash};
+use crate::state::{
+    AIR_QUALITY, CREDENTIALS, FORECAST, HOURLY, POWER_MUTEX, RTC_TIME, SYNC_STATS, WEATHER,
+    WEATHER_FETCHED,
+};
+#[cfg(feature = "tls")]
+use crate::{MutexObj, tls};
 
-static TIME_API: &str = env!("TIME_API");
-static TEMP_API: &str = env!("TEMP_API");
+/// Which weather backend to use, see `badger_core::provider`
+static WEATHER_PROVIDER: &str = or_default(option_env!("WEATHER_PROVIDER"), "open-meteo");
 
-#[derive(Format)]
-pub struct HttpError;
+/// Open-Meteo and MET Norway URLs are built from these, unless setup saved
+/// a location
+static LATITUDE: Option<&str> = option_env!("LATITUDE");
+static LONGITUDE: Option<&str> = option_env!("LONGITUDE");
+static UNITS: &str = or_default(option_env!("UNITS"), "metric");
+static WEATHER_TIMEZONE: &str = or_default(option_env!("WEATHER_TIMEZONE"), "auto");
 
+/// Hours after which the weather is marked as old, then hidden
+static WEATHER_STALE_HOURS: Option<&str> = option_env!("WEATHER_STALE_HOURS");
+static WEATHER_HIDE_HOURS: Option<&str> = option_env!("WEATHER_HIDE_HOURS");
+
+/// US AQI above which the top bar shows a warning
+static AQI_WARNING: Option<&str> = option_env!("AQI_WARNING");
+
+/// Full URL, overriding the built one. Required for the `json` provider.
+static TEMP_API: Option<&str> = option_env!("TEMP_API");
+
+/// No default, a TLS build has to say how the server is trusted
+#[cfg(feature = "tls")]
+static TLS_TRUST: &str = env!(
+    "TLS_TRUST",
+    "the tls feature needs TLS_TRUST in .env, see the HTTPS section of the README"
+);
+
+// A full TLS record is 16KiB plus overhead, our requests are much smaller
+#[cfg(feature = "tls")]
+const TLS_READ_BUFFER: usize = 16640;
+#[cfg(feature = "tls")]
+const TLS_WRITE_BUFFER: usize = 4096;
+
+/// Too big for the stack of every request, and only one runs at a time
+#[cfg(feature = "tls")]
+static TLS_BUFFERS: MutexObj<([u8; TLS_READ_BUFFER], [u8; TLS_WRITE_BUFFER])> =
+    Mutex::new(([0; TLS_READ_BUFFER], [0; TLS_WRITE_BUFFER]));
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Room for the response headers when the body is read separately
+const HEADER_BUFFER: usize = 2048;
+
+const WEATHER_BACKOFF: Backoff = Backoff {
+    max_attempts: 4,
+    initial_ms: 1000,
+    max_delay_ms: 4000,
+    min_attempt_ms: 3000,
+};
+
+/// The air quality is a nice to have, so it gets fewer tries
+const AIR_QUALITY_BACKOFF: Backoff = Backoff {
+    max_attempts: 2,
+    ..WEATHER_BACKOFF
+};
+
+const fn or_default(value: Option<&'static str>, default: &'static str) -> &'static str {
+    match value {
+        Some(value) => value,
+        None => default,
+    }
+}
+
+/// Units the weather is requested in and shown with
+pub fn units() -> Units {
+    Units::parse(UNITS).unwrap_or_default()
+}
+
+/// How long fetched weather is shown for
+pub fn weather_max_age() -> MaxAge {
+    let hours = |value: Option<&str>| value.and_then(|value| value.trim().parse().ok());
+    let default = MaxAge::default();
+
+    MaxAge {
+        stale_hours: hours(WEATHER_STALE_HOURS).unwrap_or(default.stale_hours),
+        hidden_hours: hours(WEATHER_HIDE_HOURS).unwrap_or(default.hidden_hours),
+    }
+}
+
+/// AQI that triggers the top bar warning
+pub fn aqi_warning() -> u16 {
+    AQI_WARNING
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_AQI_WARNING)
+}
+
+/// The location saved by setup, or the one built in
+async fn location() -> Option<Location> {
+    let saved = CREDENTIALS.lock().await.location;
+
+    saved.or_else(|| {
+        LATITUDE
+            .zip(LONGITUDE)
+            .and_then(|(lat, lon)| Location::parse(lat, lon))
+    })
+}
+
+fn weather_url(location: Option<Location>) -> Result<Url, HttpError> {
+    if let Some(url) = TEMP_API {
+        return Url::try_from(url).map_err(|_| HttpError::InvalidUrl);
+    }
+
+    let location = location.ok_or_else(|| {
+        error!("Set a location in setup, LATITUDE and LONGITUDE, or TEMP_API");
+        HttpError::InvalidUrl
+    })?;
+
+    match WEATHER_PROVIDER {
+        "open-meteo" => OpenMeteoQuery {
+            location,
+            units: units(),
+            timezone: WEATHER_TIMEZONE,
+            current: CurrentWeather::FIELDS,
+            daily: DailyForecast::FIELDS,
+            hourly: HourlyForecast::FIELDS,
+        }
+        .url(),
+        "met-no" => met_norway_url(location),
+        _ => Err(HttpError::InvalidUrl),
+    }
+}
+
+/// Maps errors from sending the request and reading the headers
+fn send_error(e: reqwless::Error) -> HttpError {
+    match e {
+        reqwless::Error::Dns => HttpError::Dns,
+        reqwless::Error::Codec => HttpError::Malformed,
+        reqwless::Error::BufferTooSmall => HttpError::BufferTooSmall,
+        _ => HttpError::Connect,
+    }
+}
+
+/// Maps errors from reading the body, when the connection already worked
+fn body_error(e: reqwless::Error) -> HttpError {
+    match e {
+        reqwless::Error::Codec => HttpError::Malformed,
+        reqwless::Error::BufferTooSmall => HttpError::BufferTooSmall,
+        _ => HttpError::Truncated,
+    }
+}
+
+/// Resolves the host of `target` and opens a TCP connection to it
+async fn connect<'c, T: TcpConnect>(
+    client: &'c T,
+    dns: &impl Dns,
+    target: &Target<'_>,
+) -> Result<T::Connection<'c>, HttpError> {
+    let address = dns
+        .get_host_by_name(target.host, AddrType::IPv4)
+        .await
+        .map_err(|_| HttpError::Dns)?;
+
+    client
+        .connect(SocketAddr::new(address, target.port))
+        .await
+        .map_err(|_| HttpError::Connect)
+}
+
+/// GETs `url` into `buf`, following redirects, and returns the body if it
+/// is JSON and fits. With `partial`, a body too big for `buf` is cut short
+/// instead of failing, for decoders that only need its start.
 pub async fn http_get<'a, 'b>(
     stack: &Stack<'a>,
     url: &str,
     buf: &'b mut [u8],
+    partial: bool,
 ) -> Result<&'b [u8], HttpError> {
     let dns_client = DnsSocket::new(*stack);
 
     let client_state = TcpClientState::<1, 1024, 1024>::new();
     let client = TcpClient::<'_, 1>::new(*stack, &client_state);
 
-    let mut http_client = HttpClient::new(&client, &dns_client);
+    #[cfg(feature = "tls")]
+    let trust = Trust::parse(TLS_TRUST).ok_or_else(|| {
+        error!("TLS_TRUST must be pin:HEXSHA256 or psk:IDENTITY:HEXKEY");
+        HttpError::Tls
+    })?;
 
-    let req = http_client.request(Method::GET, url).await;
+    #[cfg(feature = "tls")]
+    let mut tls_buffers = TLS_BUFFERS.lock().await;
 
-    if let Err(e) = req {
-        error!("Failed to send HTTP request: {:?}", e);
-        return Err(HttpError);
-    }
+    let capacity = if partial { usize::MAX } else { buf.len() };
+    let base = buf.as_ptr() as usize;
+    let mut url = Url::try_from(url).map_err(|_| HttpError::InvalidUrl)?;
+
+    let request = async {
+        for _ in 0..=MAX_REDIRECTS {
+            let target = Target::parse(&url)?;
 
-    let mut req = req.unwrap().headers(&[
-        ("Accept", "*/*"),
-        ("User-Agent", "Rusty-Badger/1.0"),
-        ("Connection", "close"),
-    ]);
+            if !cfg!(feature = "tls") && target.https {
+                error!("HTTPS requests need the tls feature");
+                return Err(HttpError::Tls);
+            }
 
-    let response = req.send(buf).await;
+            let next = {
+                let socket = connect(&client, &dns_client, &target).await?;
 
-    if let Err(e) = response {
-        error!("Failed to send HTTP request: {:?}", e);
-        return Err(HttpError);
-    }
+                #[cfg(feature = "tls")]
+                let mut connection = if target.https {
+                    let (read_buffer, write_buffer) = &mut *tls_buffers;
+                    let stream =
+                        tls::open(socket, target.host, &trust, read_buffer, write_buffer).await?;
+                    HttpConnection::Plain(stream)
+                } else {
+                    HttpConnection::Plain(tls::Stream::Plain(socket))
+                };
+
+                #[cfg(not(feature = "tls"))]
+                let mut connection = HttpConnection::Plain(socket);
+
+                let req = Request::get(target.path)
+                    .host(target.host)
+                    .headers(&[
+                        ("Accept", "application/json"),
+                        ("User-Agent", "Rusty-Badger/1.0"),
+                        ("Connection", "close"),
+                    ])
+                    .build();
+
+                // A partial body is read into its own part of the buffer,
+                // so reqwless can't fail it for being too long
+                let (header_buf, body_buf) = if partial {
+                    buf.split_at_mut_checked(HEADER_BUFFER)
+                        .filter(|(_, body)| !body.is_empty())
+                        .ok_or(HttpError::BufferTooSmall)?
+                } else {
+                    (&mut buf[..], &mut [][..])
+                };
+
+                let response = connection.send(req, header_buf).await.map_err(send_error)?;
 
-    let response = response.unwrap();
+                let next = check_response(
+                    &url,
+                    response.status.0,
+                    response.headers(),
+                    capacity,
+                    "application/json",
+                )?;
 
-    let body_bytes = response.body().read_to_end().await;
+                let Some(next) = next else {
+                    let body = if partial {
+                        read_prefix(response.body().reader(), body_buf).await?
+                    } else {
+                        response.body().read_to_end().await.map_err(body_error)?
+                    };
 
-    match body_bytes {
-        Ok(bytes) => Ok(bytes),
-        Err(e) => {
-            error!("Failed to read HTTP response body: {:?}", e);
-            Err(HttpError)
+                    // The body is read into `buf`, but returning the borrow
+                    // from inside the loop would hold `buf` across redirects
+                    let start = body.as_ptr() as usize - base;
+                    return Ok(start..start + body.len());
+                };
+
+                next
+            };
+
+            info!("Redirected to {}", next.as_str());
+            url = next;
         }
-    }
+
+        Err(HttpError::TooManyRedirects)
+    };
+
+    let range = with_timeout(REQUEST_TIMEOUT, request)
+        .await
+        .unwrap_or(Err(HttpError::Timeout))?;
+
+    Ok(&buf[range])
 }
 
-pub async fn fetch_api<'a, T>(stack: &Stack<'_>, rx_buf: &'a mut [u8], url: &str) -> Result<T, ()>
-where
-    T: Deserialize<'a>,
-{
-    match http_get(stack, url, rx_buf).await {
-        Ok(bytes) => match serde_json_core::de::from_slice::<T>(bytes) {
-            Ok((response, _)) => Ok(response),
-            Err(_e) => {
-                error!("Failed to parse response body");
-                Err(())
-            }
-        },
-        Err(e) => {
-            error!("Failed to make API request: {:?}", e);
-            Err(())
+/// Reads until `buf` is full or the body ends
+async fn read_prefix<'b>(mut reader: impl Read, buf: &'b mut [u8]) -> Result<&'b [u8], HttpError> {
+    let mut len = 0;
+
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]).await {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return Err(HttpError::Truncated),
         }
     }
+
+    Ok(&buf[..len])
 }
 
-pub async fn fetch_time(stack: &Stack<'_>, rx_buf: &mut [u8], rtc_device: &'static RtcDevice) {
-    let _guard = POWER_MUTEX.lock().await;
+/// Fetches `url` and decodes it with `parse`, retrying transient failures
+/// while `deadline` allows. `retry` is left holding the number of attempts
+/// made.
+pub async fn fetch_api<T>(
+    stack: &Stack<'_>,
+    rx_buf: &mut [u8],
+    url: &str,
+    partial: bool,
+    parse: impl Fn(&[u8]) -> Result<T, HttpError>,
+    retry: &mut Retry,
+    deadline: Instant,
+) -> Result<T, HttpError> {
+    loop {
+        retry.start_attempt();
+
+        let error = match http_get(stack, url, rx_buf, partial).await.and_then(&parse) {
+            Ok(response) => return Ok(response),
+            Err(e) => e,
+        };
+
+        let remaining_ms = deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis();
 
-    if let Ok(response) = fetch_api::<TimeApiResponse>(stack, rx_buf, TIME_API).await {
-        set_time(rtc_device, response.into()).await;
+        let Some(delay) = retry.next_delay(&error, RoscRng.next_u32(), remaining_ms) else {
+            return Err(error);
+        };
+
+        warn!(
+            "Attempt {} failed: {:?}, retrying in {}ms",
+            retry.attempts(),
+            error,
+            delay
+        );
+        Timer::after_millis(delay.into()).await;
     }
 }
 
 pub async fn fetch_weather(
     stack: &Stack<'_>,
     rx_buf: &mut [u8],
-    flash_device: &'static FlashDevice,
-) {
+    deadline: Instant,
+) -> Result<(), HttpError> {
     let _guard = POWER_MUTEX.lock().await;
 
-    if let Ok(response) = fetch_api::<OpenMeteoResponse>(stack, rx_buf, TEMP_API).await {
-        let weather = response.current;
+    let url = weather_url(location().await)?;
 
-        info!(
-            "Temp: {}C, Code: {}",
-            weather.temperature, weather.weathercode
-        );
+    let Some(provider) = Provider::from_config(WEATHER_PROVIDER, &url, units()) else {
+        error!("WEATHER_PROVIDER must be open-meteo, met-no or json");
+        return Err(HttpError::InvalidUrl);
+    };
 
-        {
-            let mut data = WEATHER.lock().await;
-            *data = Some(weather);
-        }
+    let mut retry = Retry::new(WEATHER_BACKOFF);
+    let result = fetch_api(
+        stack,
+        rx_buf,
+        provider.url(),
+        provider.partial_body(),
+        |body| provider.parse(body),
+        &mut retry,
+        deadline,
+    )
+    .await;
 
-        flash::save_state(flash_device).await;
-    }
-}
+    SYNC_STATS.lock().await.weather_attempts = retry.attempts();
 
-#[derive(Deserialize)]
-struct TimeApiResponse<'a> {
-    datetime: &'a str,
-}
+    let report = result.inspect_err(|e| error!("Weather fetch failed: {:?}", e))?;
+    let weather = report.current;
+
+    info!(
+        "Temp: {}{}, Code: {}, Forecast days: {}",
+        weather.temperature,
+        units().temperature_symbol(),
+        weather.weathercode,
+        report.forecast.as_ref().map_or(0, |f| f.len())
+    );
 
-impl<'a> From<TimeApiResponse<'a>> for PrimitiveDateTime {
-    fn from(response: TimeApiResponse) -> Self {
-        info!("Datetime: {:?}", response.datetime);
-        //split at T
-        let datetime = response.datetime.split('T').collect::<Vec<&str, 2>>();
-        //split at -
-        let date = datetime[0].split('-').collect::<Vec<&str, 3>>();
-        let year = date[0].parse::<i32>().unwrap();
-        let month = date[1].parse::<u8>().unwrap();
-        let day = date[2].parse::<u8>().unwrap();
-        //split at :
-        let time = datetime[1].split(':').collect::<Vec<&str, 4>>();
-        let hour = time[0].parse::<u8>().unwrap();
-        let minute = time[1].parse::<u8>().unwrap();
-        //split at .
-        let second_split = time[2].split('.').collect::<Vec<&str, 2>>();
-        let second = second_split[0].parse::<u8>().unwrap();
-
-        let date = Date::from_calendar_date(year, Month::try_from(month).unwrap(), day).unwrap();
-        let time = Time::from_hms(hour, minute, second).unwrap();
-
-        PrimitiveDateTime::new(date, time)
+    {
+        let mut data = WEATHER.lock().await;
+        *data = Some(weather);
     }
+
+    // The clock was just set by the SNTP request that runs alongside
+    *WEATHER_FETCHED.lock().await = *RTC_TIME.lock().await;
+
+    *FORECAST.lock().await = report.forecast;
+    *HOURLY.lock().await = report.hourly;
+
+    Ok(())
 }
 
-#[derive(Deserialize)]
-pub struct OpenMeteoResponse {
-    pub current: CurrentWeather,
+/// Fetches the air quality from Open-Meteo. It needs a location whatever the
+/// weather provider, and is skipped without one.
+pub async fn fetch_air_quality(
+    stack: &Stack<'_>,
+    rx_buf: &mut [u8],
+    deadline: Instant,
+) -> Result<(), HttpError> {
+    let _guard = POWER_MUTEX.lock().await;
+
+    let Some(location) = location().await else {
+        return Ok(());
+    };
+    let url = air_quality_url(location, AirQuality::FIELDS)?;
+
+    let mut retry = Retry::new(AIR_QUALITY_BACKOFF);
+    let result = fetch_api(
+        stack,
+        rx_buf,
+        &url,
+        false,
+        |body| parse_json::<OpenMeteoAirQualityResponse>(body).map(|r| r.current),
+        &mut retry,
+        deadline,
+    )
+    .await;
+
+    SYNC_STATS.lock().await.air_quality_attempts = retry.attempts();
+
+    let air_quality = result.inspect_err(|e| error!("Air quality fetch failed: {:?}", e))?;
+
+    info!(
+        "US AQI: {:?}, PM2.5: {:?}",
+        air_quality.aqi(),
+        air_quality.pm2_5
+    );
+
+    *AIR_QUALITY.lock().await = Some(air_quality);
+
+    Ok(())
 }
@@ -5,25 +5,35 @@ mod battery;
 mod buttons;
 mod display;
 mod flash;
-mod helpers;
 mod http;
 mod image;
 mod led;
+mod provision;
+mod sntp;
 mod state;
 mod time;
+#[cfg(feature = "tls")]
+mod tls;
 mod wifi;
 
-use crate::battery::{BatteryState, get_power_state};
+use core::sync::atomic::Ordering;
+
+use crate::battery::get_power_state;
 use crate::buttons::{handle_presses, listen_to_button};
 use crate::flash::FlashDriver;
-use crate::image::Shift;
 use crate::led::blink;
-use crate::state::{Button, DISPLAY_CHANGED, POWER_INFO, POWER_MUTEX, Screen};
-use crate::time::{check_trust_time, get_time, update_time};
+use crate::state::{
+    Button, CREDENTIALS, CURRENT_PAGE, DISPLAY_CHANGED, LAST_SYNC, POWER_INFO, POWER_MUTEX,
+};
+use crate::time::{TRUST_TIME, check_trust_time, get_time, timezone, to_local, update_time};
+use badger_core::battery::BatteryState;
+use badger_core::layout::Screen;
+use badger_core::schedule::hourly_alarm_minute;
+use badger_core::wake::{self, Alarm, WakeCause};
 use cyw43_pio::{DEFAULT_CLOCK_DIVIDER, PioSpi};
 use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
 use embassy_executor::Spawner;
-use embassy_futures::join::join;
+use embassy_futures::join::{join, join5};
 use embassy_net::StackResources;
 use embassy_rp::adc;
 use embassy_rp::clocks::RoscRng;
@@ -36,7 +46,7 @@ use embassy_rp::spi::Spi;
 use embassy_rp::{bind_interrupts, gpio, i2c, pio, spi};
 use embassy_sync::blocking_mutex::raw::{NoopRawMutex, ThreadModeRawMutex};
 use embassy_sync::mutex::Mutex;
-use embassy_time::Timer;
+use embassy_time::{Instant, Timer};
 use gpio::{Level, Output, Pull};
 use pcf85063a::{Control, PCF85063};
 use static_cell::StaticCell;
@@ -89,11 +99,7 @@ async fn main(spawner: Spawner) {
     let rtc_device;
     let flash_device;
     let user_led;
-
-    let mut sync_wifi = false;
-    let mut is_rtc_alarm = false;
-    let mut screen_refresh_type = Screen::None;
-    let mut image_dir = Shift::None;
+    let plan;
 
     // Button handlers
     let mut up = Input::new(p.PIN_15, Pull::Down);
@@ -103,33 +109,24 @@ async fn main(spawner: Spawner) {
     let mut c = Input::new(p.PIN_14, Pull::Down);
     let rtc_alarm = Input::new(p.PIN_8, Pull::Down);
 
-    if up.is_high() {
-        // Up
-        image_dir = Shift::Prev;
-        screen_refresh_type = Screen::Image;
-        up.wait_for_low().await;
-    } else if down.is_high() {
-        // Down
-        image_dir = Shift::Next;
-        screen_refresh_type = Screen::Image;
-        down.wait_for_low().await;
-    } else if a.is_high() {
-        // A
-        sync_wifi = true;
-        screen_refresh_type = Screen::TopBar;
-        a.wait_for_low().await;
-    } else if b.is_high() {
-        // B
-        screen_refresh_type = Screen::Full;
-        b.wait_for_low().await;
-    } else if c.is_high() {
-        // C
-        screen_refresh_type = Screen::TopBar;
-        c.wait_for_low().await;
-    } else if rtc_alarm.is_high() {
-        // RTC wake
-        is_rtc_alarm = true;
-    }
+    let cause = WakeCause {
+        up: up.is_high(),
+        down: down.is_high(),
+        a: a.is_high(),
+        b: b.is_high(),
+        c: c.is_high(),
+        rtc_alarm: rtc_alarm.is_high(),
+    };
+
+    // Let go of every button that woke us before carrying on
+    join5(
+        up.wait_for_low(),
+        down.wait_for_low(),
+        a.wait_for_low(),
+        b.wait_for_low(),
+        c.wait_for_low(),
+    )
+    .await;
 
     // User LED
     {
@@ -144,6 +141,13 @@ async fn main(spawner: Spawner) {
         flash_device = FLASH_DEVICE.init(Mutex::new(flashdev));
 
         flash::load_state(flash_device).await;
+
+        let mut credentials =
+            flash::load_credentials(flash_device, wifi::default_credentials()).await;
+        if wifi::apply_static_ipv4(&mut credentials) {
+            flash::save_credentials(flash_device, &credentials).await;
+        }
+        *CREDENTIALS.lock().await = credentials;
     }
 
     // I2C RTC
@@ -162,24 +166,28 @@ async fn main(spawner: Spawner) {
 
         let mut rtc = rtc_device.lock().await;
 
-        if is_rtc_alarm {
-            let now = rtc.get_datetime().await;
-
-            match now {
-                Ok(now) if now.minute() == 0 => {
-                    sync_wifi = true;
-                    screen_refresh_type = Screen::Full;
-                }
-                _ => {
-                    screen_refresh_type = Screen::TopBar;
-                }
-            }
-        }
+        // Pull image index and page from RTC ram byte
+        let (index, page) = wake::from_ram_byte(rtc.read_ram_byte().await.unwrap_or(0));
+
+        let utc = rtc.get_datetime().await.ok();
+        let time_trusted = TRUST_TIME.load(Ordering::Relaxed);
+
+        // Both times are UTC, and only comparable once the clock is set
+        let since_sync = utc
+            .zip(*LAST_SYNC.lock().await)
+            .filter(|_| time_trusted)
+            .map(|(now, last)| now - last);
 
-        // Pull image index from RTC ram byte, shift if we need, save it
-        image::set(rtc.read_ram_byte().await.unwrap_or(0) as usize);
-        image::shift(image_dir);
-        rtc.write_ram_byte(image::get() as u8).await.ok();
+        plan = wake::plan(cause, utc.map(to_local), time_trusted, page, since_sync);
+        defmt::info!("Wake plan: {}", plan);
+
+        // Shift if we need, save it
+        image::set(index);
+        image::shift(plan.image_shift);
+        *CURRENT_PAGE.lock().await = plan.page;
+        rtc.write_ram_byte(wake::to_ram_byte(image::get(), plan.page))
+            .await
+            .ok();
     }
 
     // Long running tasks if we're on mains power
@@ -230,7 +238,7 @@ async fn main(spawner: Spawner) {
     }
 
     // Connect to wifi and sync
-    if sync_wifi || external_power {
+    if plan.sync_wifi || external_power {
         let pwr = Output::new(p.PIN_23, Level::Low);
         let cs = Output::new(p.PIN_25, Level::High);
         let mut pio = Pio::new(p.PIO0, Irqs);
@@ -254,7 +262,13 @@ async fn main(spawner: Spawner) {
             .set_power_management(cyw43::PowerManagementMode::PowerSave)
             .await;
 
-        let config = embassy_net::Config::dhcpv4(Default::default());
+        // Setup serves its own access point from a fixed address. Otherwise
+        // each network brings its own, DHCP or static, when it's joined
+        let config = if plan.provision {
+            provision::ap_config()
+        } else {
+            embassy_net::Config::default()
+        };
 
         let (stack, netrunner) = embassy_net::new(
             net_device,
@@ -265,6 +279,10 @@ async fn main(spawner: Spawner) {
 
         spawner.must_spawn(net_task(netrunner));
 
+        if plan.provision {
+            provision::run(&mut control, stack, user_led, flash_device).await;
+        }
+
         if external_power {
             spawner
                 .spawn(wifi::run(
@@ -281,9 +299,10 @@ async fn main(spawner: Spawner) {
     }
 
     if !external_power {
-        DISPLAY_CHANGED.signal(screen_refresh_type);
+        DISPLAY_CHANGED.signal(plan.screen);
         Timer::after_secs(3).await;
-        nighty_night(&mut power_latch, rtc_device).await;
+        defmt::info!("Awake for {} ms", Instant::now().as_millis());
+        nighty_night(&mut power_latch, rtc_device, plan.next_alarm).await;
     }
 }
 
@@ -299,7 +318,11 @@ async fn cyw43_task(
     runner.run().await
 }
 
-async fn nighty_night(power_latch: &mut Output<'static>, rtc_device: &'static RtcDevice) {
+async fn nighty_night(
+    power_latch: &mut Output<'static>,
+    rtc_device: &'static RtcDevice,
+    alarm: Alarm,
+) {
     DISPLAY_CHANGED.signal(Screen::Shutdown);
 
     let mut rtc = rtc_device.lock().await;
@@ -307,7 +330,9 @@ async fn nighty_night(power_latch: &mut Output<'static>, rtc_device: &'static Rt
     rtc.disable_all_alarms().await.ok();
     rtc.clear_alarm_flag().await.ok();
 
-    if let Ok(now) = rtc.get_datetime().await
+    let now = rtc.get_datetime().await.ok();
+
+    if let Some(now) = now
         && now.second() == 0
     {
         Timer::after_millis(1000 - now.millisecond() as u64).await
@@ -315,6 +340,13 @@ async fn nighty_night(power_latch: &mut Output<'static>, rtc_device: &'static Rt
 
     rtc.set_alarm_seconds(0).await.ok();
     rtc.control_alarm_seconds(Control::On).await.ok();
+
+    if alarm == Alarm::EveryHour {
+        // The RTC runs on UTC, so half hour zones need a different minute
+        let minute = now.map_or(0, |now| hourly_alarm_minute(timezone().offset_at(now)));
+        rtc.set_alarm_minutes(minute).await.ok();
+        rtc.control_alarm_minutes(Control::On).await.ok();
+    }
     rtc.control_alarm_interrupt(Control::On).await.ok();
 
     Timer::after_secs(1).await;
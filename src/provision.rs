@@ -0,0 +1,222 @@
+use badger_core::layout::Screen;
+use badger_core::provision::{
+    AP_ADDRESS, AP_SSID, HTTP_PORT, REQUEST_SIZE, Request, RequestError, SETUP_URL, Setup, Status,
+    form_page, not_found_page, parse_form, parse_request, response_head, saved_page,
+};
+use badger_core::{dhcp, dns};
+use cyw43::Control;
+use defmt::error;
+use embassy_futures::select::{Either3, select3};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, Ipv4Address, Ipv4Cidr, Stack, StaticConfigV4};
+use embassy_time::{Duration, Timer};
+use embedded_io_async::Write;
+use heapless::Vec;
+use log::info;
+
+use crate::{
+    FlashDevice, UserLed, flash, led,
+    state::{CREDENTIALS, DISPLAY_CHANGED, LAST_NETWORK, POWER_MUTEX},
+};
+
+/// Channel of the setup access point
+const AP_CHANNEL: u8 = 6;
+
+/// Setup gives up after this long without a saved form, so a forgotten
+/// badge doesn't drain its battery
+const SETUP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A connection that stalls this long is dropped for the next one
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fixed address for the stack while it serves the access point
+pub fn ap_config() -> embassy_net::Config {
+    embassy_net::Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(Ipv4Address::from(AP_ADDRESS), 24),
+        gateway: None,
+        dns_servers: Vec::new(),
+    })
+}
+
+/// Runs setup: starts the access point, serves the form until it's saved or
+/// [`SETUP_TIMEOUT`] runs out, then puts the badge back. A saved network
+/// becomes the first one tried.
+pub async fn run(
+    control: &mut Control<'static>,
+    stack: Stack<'static>,
+    user_led: &'static UserLed,
+    flash_driver: &'static FlashDevice,
+) {
+    {
+        let _guard = POWER_MUTEX.lock().await;
+        control.start_ap_open(AP_SSID, AP_CHANNEL).await;
+    }
+
+    info!("Setup started, join {} and open {}", AP_SSID, SETUP_URL);
+    DISPLAY_CHANGED.signal(Screen::Provisioning);
+
+    let result = select3(
+        led::loop_breathe(user_led),
+        Timer::after(SETUP_TIMEOUT),
+        select3(serve_dhcp(stack), serve_dns(stack), serve_form(stack)),
+    )
+    .await;
+
+    control.close_ap().await;
+    DISPLAY_CHANGED.signal(Screen::Full);
+
+    let Either3::Third(Either3::Third(setup)) = result else {
+        info!("Setup ended without saving");
+        return;
+    };
+
+    let credentials = {
+        let mut credentials = CREDENTIALS.lock().await;
+        credentials.add(setup.network.clone());
+        if setup.location.is_some() {
+            credentials.location = setup.location;
+        }
+        credentials.clone()
+    };
+
+    flash::save_credentials(flash_driver, &credentials).await;
+    *LAST_NETWORK.lock().await = Some(setup.network.ssid.clone());
+
+    info!("Setup saved {}", setup.network.ssid.as_str());
+}
+
+/// Hands every client that asks an address on the access point
+async fn serve_dhcp(stack: Stack<'_>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 2];
+    let mut rx_buffer = [0; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 2];
+    let mut tx_buffer = [0; 1024];
+
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    if socket.bind(dhcp::SERVER_PORT).is_err() {
+        error!("Failed to bind DHCP socket");
+        return;
+    }
+
+    let mut request = [0; 576];
+    let mut reply = [0; dhcp::REPLY_SIZE];
+
+    loop {
+        let Ok((len, _)) = socket.recv_from(&mut request).await else {
+            continue;
+        };
+
+        if let Some(len) = dhcp::reply(&request[..len], AP_ADDRESS, &mut reply) {
+            let broadcast = (IpAddress::Ipv4(Ipv4Address::BROADCAST), dhcp::CLIENT_PORT);
+            socket.send_to(&reply[..len], broadcast).await.ok();
+        }
+    }
+}
+
+/// Answers every name with the access point's address, so any URL leads to
+/// the form
+async fn serve_dns(stack: Stack<'_>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 2];
+    let mut rx_buffer = [0; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 2];
+    let mut tx_buffer = [0; 1024];
+
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    if socket.bind(dns::SERVER_PORT).is_err() {
+        error!("Failed to bind DNS socket");
+        return;
+    }
+
+    let mut query = [0; 512];
+    let mut reply = [0; dns::REPLY_SIZE];
+
+    loop {
+        let Ok((len, client)) = socket.recv_from(&mut query).await else {
+            continue;
+        };
+
+        if let Some(len) = dns::reply(&query[..len], AP_ADDRESS, &mut reply) {
+            socket.send_to(&reply[..len], client).await.ok();
+        }
+    }
+}
+
+/// Serves the form one connection at a time until one saves it
+async fn serve_form(stack: Stack<'_>) -> Setup {
+    let mut rx_buffer = [0; REQUEST_SIZE];
+    let mut tx_buffer = [0; 1024];
+    let mut request = [0; REQUEST_SIZE];
+
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(CLIENT_TIMEOUT));
+
+        if socket.accept(HTTP_PORT).await.is_err() {
+            continue;
+        }
+
+        let setup = respond(&mut socket, &mut request).await;
+
+        socket.close();
+        socket.flush().await.ok();
+
+        if let Some(setup) = setup {
+            return setup;
+        }
+    }
+}
+
+/// Reads one request and answers it, returning the form if it was valid
+async fn respond(socket: &mut TcpSocket<'_>, buf: &mut [u8; REQUEST_SIZE]) -> Option<Setup> {
+    let mut len = 0;
+
+    // A full buffer never parses as incomplete, so there's always room
+    loop {
+        match socket.read(&mut buf[len..]).await {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => len += n,
+        }
+
+        if parse_request(&buf[..len]) != Err(RequestError::Incomplete) {
+            break;
+        }
+    }
+
+    let (status, page, setup) = match parse_request(&buf[..len]) {
+        Ok(Request::Form) => (Status::Ok, form_page(None), None),
+        Ok(Request::Submit(body)) => match parse_form(body) {
+            Ok(setup) => (Status::Ok, saved_page(), Some(setup)),
+            Err(e) => {
+                info!("Setup form rejected: {}", e.message());
+                (Status::BadRequest, form_page(Some(e)), None)
+            }
+        },
+        Ok(Request::NotFound) => (Status::NotFound, not_found_page(), None),
+        Err(_) => (Status::BadRequest, not_found_page(), None),
+    };
+
+    let head = response_head(status, page.len());
+    let sent = async {
+        socket.write_all(head.as_bytes()).await?;
+        socket.write_all(page.as_bytes()).await
+    }
+    .await;
+
+    // Only count the form as saved once the browser has been told
+    sent.ok().and(setup)
+}
@@ -0,0 +1,106 @@
+use badger_core::sntp::{self, NTP_PORT, SntpError, Transport};
+use defmt::{error, info};
+use embassy_net::Stack;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_rp::clocks::RoscRng;
+use embassy_time::{Duration, Instant, with_timeout};
+use heapless::Vec;
+
+use crate::RtcDevice;
+use crate::state::POWER_MUTEX;
+use crate::time::set_time;
+
+static NTP_SERVERS: &str = env!("NTP_SERVERS");
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_ROUND_TRIP_US: u64 = 1_000_000;
+
+struct UdpTransport<'a> {
+    stack: Stack<'a>,
+    socket: UdpSocket<'a>,
+}
+
+impl Transport for UdpTransport<'_> {
+    async fn exchange(
+        &mut self,
+        server: &str,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, SntpError> {
+        let addrs = self
+            .stack
+            .dns_query(server, DnsQueryType::A)
+            .await
+            .map_err(|_| SntpError::Transport)?;
+        let addr = *addrs.first().ok_or(SntpError::Transport)?;
+
+        self.socket
+            .send_to(request, (addr, NTP_PORT))
+            .await
+            .map_err(|_| SntpError::Transport)?;
+
+        match with_timeout(REPLY_TIMEOUT, self.socket.recv_from(response)).await {
+            Ok(Ok((len, _))) => Ok(len),
+            _ => Err(SntpError::Transport),
+        }
+    }
+}
+
+pub async fn fetch_time(
+    stack: &Stack<'_>,
+    rtc_device: &'static RtcDevice,
+) -> Result<(), SntpError> {
+    let _guard = POWER_MUTEX.lock().await;
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; 128];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; 128];
+
+    let mut socket = UdpSocket::new(
+        *stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    if socket.bind(0).is_err() {
+        error!("Failed to bind NTP socket");
+        return Err(SntpError::Transport);
+    }
+
+    let servers = NTP_SERVERS
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .take(8)
+        .collect::<Vec<&str, 8>>();
+
+    let mut transport = UdpTransport {
+        stack: *stack,
+        socket,
+    };
+
+    let result = sntp::query(
+        &mut transport,
+        &servers,
+        || RoscRng.next_u64(),
+        || Instant::now().as_micros(),
+        MAX_ROUND_TRIP_US,
+    )
+    .await;
+
+    match result {
+        Ok(utc) => {
+            info!("NTP sync succeeded");
+            set_time(rtc_device, utc).await;
+            Ok(())
+        }
+        Err(e) => {
+            error!("NTP sync failed: {:?}", e);
+            Err(e)
+        }
+    }
+}
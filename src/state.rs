@@ -1,25 +1,27 @@
+use badger_core::{
+    air::AirQuality,
+    battery::BatteryState,
+    layout::{Page, Screen},
+    storage::Credentials,
+    sync::{SyncError, SyncStats},
+    weather::{CurrentWeather, Forecast, HourlyForecast},
+    wifi::{Reconnect, SSID_LEN},
+};
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use heapless::{String, Vec};
 use portable_atomic::AtomicUsize;
-use serde::{Deserialize, Serialize};
 use time::PrimitiveDateTime;
 
-use crate::{MutexObj, battery::BatteryState};
+use crate::MutexObj;
 
 pub static POWER_MUTEX: MutexObj<()> = Mutex::new(());
 pub static POWER_INFO: MutexObj<Option<BatteryState>> = Mutex::new(None);
 
 pub static RTC_TIME: MutexObj<Option<PrimitiveDateTime>> = Mutex::new(None);
 
-#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
-pub enum Screen {
-    None,
-    TopBar,
-    Image,
-    Full,
-    Shutdown,
-}
 pub static DISPLAY_CHANGED: Signal<ThreadModeRawMutex, Screen> = Signal::new();
 pub static CURRENT_IMAGE: AtomicUsize = AtomicUsize::new(0);
+pub static CURRENT_PAGE: MutexObj<Page> = Mutex::new(Page::Image);
 
 pub enum Button {
     A,
@@ -30,12 +32,38 @@ pub enum Button {
 }
 pub static BUTTON_PRESSED: Signal<ThreadModeRawMutex, &'static Button> = Signal::new();
 
-#[derive(Serialize, Deserialize, Copy, Clone)]
-pub struct CurrentWeather {
-    pub temperature: f32,
-    pub weathercode: u8,
-    pub relative_humidity_2m: f32,
-    // pub is_day: u8,
-}
 pub static WEATHER: MutexObj<Option<CurrentWeather>> = Mutex::new(None);
+/// UTC time `WEATHER` was fetched, if the clock was set at the time
+pub static WEATHER_FETCHED: MutexObj<Option<PrimitiveDateTime>> = Mutex::new(None);
+pub static FORECAST: MutexObj<Option<Forecast>> = Mutex::new(None);
+pub static HOURLY: MutexObj<Option<HourlyForecast>> = Mutex::new(None);
+pub static AIR_QUALITY: MutexObj<Option<AirQuality>> = Mutex::new(None);
 pub static UPDATE_WEATHER: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// UTC time of the last sync that got the weather, kept in flash so the
+/// wake planner can retry a missed one
+pub static LAST_SYNC: MutexObj<Option<PrimitiveDateTime>> = Mutex::new(None);
+
+/// WiFi networks to join and the location from setup, from their own flash
+/// record
+pub static CREDENTIALS: MutexObj<Credentials> = Mutex::new(Credentials {
+    networks: Vec::new(),
+    location: None,
+});
+
+/// SSID of the last network joined, tried first on the next sync
+pub static LAST_NETWORK: MutexObj<Option<String<SSID_LEN>>> = Mutex::new(None);
+
+/// Lease from the last full join, reused by the next one while it's recent
+pub static RECONNECT: MutexObj<Option<Reconnect>> = Mutex::new(None);
+
+/// Outcome of the last WiFi sync, shown in the top bar until one succeeds
+pub static SYNC_ERROR: MutexObj<Option<SyncError>> = Mutex::new(None);
+pub static SYNC_STATS: MutexObj<SyncStats> = Mutex::new(SyncStats {
+    weather_attempts: 0,
+    air_quality_attempts: 0,
+    fast_reconnect: false,
+    join_ms: 0,
+    network_ms: 0,
+    sync_ms: 0,
+});
@@ -1,3 +1,6 @@
+use badger_core::layout::Screen;
+use badger_core::schedule::secs_until_next_minute;
+use badger_core::tz::TimeZone;
 use core::sync::atomic::Ordering;
 use embassy_time::Timer;
 use portable_atomic::AtomicBool;
@@ -5,11 +8,22 @@ use time::PrimitiveDateTime;
 
 use crate::{
     RtcDevice,
-    state::{DISPLAY_CHANGED, POWER_MUTEX, RTC_TIME, Screen},
+    state::{DISPLAY_CHANGED, POWER_MUTEX, RTC_TIME},
 };
 
+static TIMEZONE: &str = env!("TIMEZONE");
+
 pub static TRUST_TIME: AtomicBool = AtomicBool::new(false);
 
+/// The RTC keeps UTC, this is the zone everything is shown in
+pub fn timezone() -> TimeZone {
+    TimeZone::parse(TIMEZONE).unwrap_or(TimeZone::UTC)
+}
+
+pub fn to_local(utc: PrimitiveDateTime) -> PrimitiveDateTime {
+    timezone().to_local(utc)
+}
+
 pub async fn get_time(rtc_device: &'static RtcDevice) {
     if !TRUST_TIME.load(Ordering::Relaxed) {
         return;
@@ -51,10 +65,7 @@ pub async fn check_trust_time(rtc_device: &'static RtcDevice) {
 #[embassy_executor::task]
 pub async fn update_time(rtc_device: &'static RtcDevice) -> ! {
     loop {
-        let delay = match *RTC_TIME.lock().await {
-            Some(time) => 60 - time.second().clamp(0, 50),
-            None => 60,
-        } as u64;
+        let delay = secs_until_next_minute(*RTC_TIME.lock().await);
 
         Timer::after_secs(delay).await;
         get_time(rtc_device).await;
@@ -0,0 +1,128 @@
+//! Opens TLS connections for `http.rs`. reqwless only offers embedded-tls
+//! without a verifier or with a pre-shared key, so connections are opened
+//! here with the pin verifier from `badger_core::tls` and handed to reqwless
+//! as a plain stream.
+
+use badger_core::http::HttpError;
+use badger_core::tls::{PinnedProvider, Trust};
+use defmt::{Debug2Format, error};
+use embassy_rp::clocks::RoscRng;
+use embedded_io_async::{Error, ErrorKind, ErrorType, Read, Write};
+use embedded_io_async_07 as io07;
+use embedded_tls::{
+    Aes128GcmSha256, TlsConfig, TlsConnection, TlsContext, TlsError, UnsecureProvider,
+};
+
+/// What reqwless reads and writes, with or without TLS
+// There's no heap to box the connection in, and only one request at a time
+#[allow(clippy::large_enum_variant)]
+pub enum Stream<'b, C: Read + Write + 'b> {
+    Plain(C),
+    Tls(TlsConnection<'b, Socket<C>, Aes128GcmSha256>),
+}
+
+/// Runs the TLS handshake with `host` over `socket`, checking the server as
+/// `trust` says
+pub async fn open<'b, C: Read + Write + 'b>(
+    socket: C,
+    host: &str,
+    trust: &Trust<'_>,
+    read_buffer: &'b mut [u8],
+    write_buffer: &'b mut [u8],
+) -> Result<Stream<'b, C>, HttpError> {
+    let mut connection = TlsConnection::new(Socket(socket), read_buffer, write_buffer);
+    let config = TlsConfig::new().with_server_name(host);
+
+    let opened = match trust {
+        // The key authenticates the server, so there's nothing to verify
+        Trust::Psk { identity, key } => {
+            let config = config.with_psk(key, &[identity.as_bytes()]);
+            let provider = UnsecureProvider::new::<Aes128GcmSha256>(RoscRng);
+            connection.open(TlsContext::new(&config, provider)).await
+        }
+        Trust::Pin(pin) => {
+            let mut provider = PinnedProvider::new(RoscRng, pin);
+            let opened = connection
+                .open(TlsContext::new(&config, &mut provider))
+                .await;
+
+            match opened {
+                Ok(()) if !provider.verified() => {
+                    error!("Server didn't prove it holds the pinned key");
+                    Err(TlsError::InvalidCertificate)
+                }
+                opened => opened,
+            }
+        }
+    };
+
+    opened.map_err(|e| {
+        error!("TLS handshake failed: {:?}", Debug2Format(&e));
+        HttpError::Tls
+    })?;
+
+    Ok(Stream::Tls(connection))
+}
+
+impl<'b, C: Read + Write + 'b> ErrorType for Stream<'b, C> {
+    type Error = ErrorKind;
+}
+
+impl<'b, C: Read + Write + 'b> Read for Stream<'b, C> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        match self {
+            Self::Plain(socket) => socket.read(buf).await.map_err(|e| e.kind()),
+            Self::Tls(connection) => match connection.read(buf).await {
+                // The server's close_notify, the end of a `Connection: close`
+                // response
+                Err(TlsError::ConnectionClosed) => Ok(0),
+                read => read.map_err(tls_error),
+            },
+        }
+    }
+}
+
+impl<'b, C: Read + Write + 'b> Write for Stream<'b, C> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, ErrorKind> {
+        match self {
+            Self::Plain(socket) => socket.write(buf).await.map_err(|e| e.kind()),
+            Self::Tls(connection) => connection.write(buf).await.map_err(tls_error),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), ErrorKind> {
+        match self {
+            Self::Plain(socket) => socket.flush().await.map_err(|e| e.kind()),
+            Self::Tls(connection) => connection.flush().await.map_err(tls_error),
+        }
+    }
+}
+
+fn tls_error(e: TlsError) -> ErrorKind {
+    error!("TLS failed: {:?}", Debug2Format(&e));
+    ErrorKind::Other
+}
+
+/// embassy's sockets implement embedded-io-async 0.6, embedded-tls wants
+/// 0.7. The traits are the same, only the error kinds are lost.
+pub struct Socket<C>(C);
+
+impl<C: Read + Write> io07::ErrorType for Socket<C> {
+    type Error = io07::ErrorKind;
+}
+
+impl<C: Read + Write> io07::Read for Socket<C> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, io07::ErrorKind> {
+        self.0.read(buf).await.map_err(|_| io07::ErrorKind::Other)
+    }
+}
+
+impl<C: Read + Write> io07::Write for Socket<C> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, io07::ErrorKind> {
+        self.0.write(buf).await.map_err(|_| io07::ErrorKind::Other)
+    }
+
+    async fn flush(&mut self) -> Result<(), io07::ErrorKind> {
+        self.0.flush().await.map_err(|_| io07::ErrorKind::Other)
+    }
+}
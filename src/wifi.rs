@@ -1,52 +1,339 @@
-use cyw43::{Control, JoinOptions};
-use embassy_futures::{join::join, select::select};
-use embassy_net::Stack;
-use embassy_time::{Duration, Timer, with_timeout};
+use badger_core::layout::Screen;
+use badger_core::storage::{Credentials, to_unix_time};
+use badger_core::sync::{SyncError, SyncStats};
+use badger_core::wifi::{
+    AccessPoint, Ipv4Settings, MAX_DNS_SERVERS, Network, Reconnect, Scan, parse_networks,
+    parse_static_ipv4,
+};
+use cyw43::{Control, JoinOptions, ScanOptions};
+use defmt::error;
+use embassy_futures::{
+    join::join,
+    select::{Either, select},
+};
+use embassy_net::{ConfigV4, Ipv4Address, Ipv4Cidr, Stack, StaticConfigV4};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use heapless::String;
 use log::info;
 
 use crate::{
-    FlashDevice, RtcDevice, UserLed,
-    http::{fetch_time, fetch_weather},
+    FlashDevice, RtcDevice, UserLed, flash,
+    http::{fetch_air_quality, fetch_weather},
     led,
-    state::{DISPLAY_CHANGED, POWER_MUTEX, Screen, UPDATE_WEATHER},
+    sntp::fetch_time,
+    state::{
+        CREDENTIALS, DISPLAY_CHANGED, LAST_NETWORK, LAST_SYNC, POWER_MUTEX, RECONNECT, RTC_TIME,
+        SYNC_ERROR, SYNC_STATS, UPDATE_WEATHER,
+    },
 };
 
 pub static FW: &[u8] = include_bytes!("../cyw43-firmware/43439A0.bin");
 pub static CLM: &[u8] = include_bytes!("../cyw43-firmware/43439A0_clm.bin");
 
-static WIFI_SSID: &str = env!("WIFI_SSID");
-static WIFI_PASSWORD: &[u8] = include_bytes!("../.wifi");
+/// Comma separated, highest priority first, see `badger_core::wifi`. Only
+/// written to flash on first boot, after that the flash record is used.
+static WIFI_SSID: &str = match option_env!("WIFI_SSID") {
+    Some(ssids) => ssids,
+    None => "",
+};
+/// One password per line, in the same order. Copied from `.wifi` by the
+/// build script, empty without one.
+static WIFI_PASSWORD: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/wifi"));
+/// `;` separated `SSID=address/prefix,gateway,dns,...` for the networks
+/// that don't use DHCP, see `badger_core::wifi::Ipv4Settings`. Applied to
+/// the flash record on every boot.
+static WIFI_IPV4: &str = match option_env!("WIFI_IPV4") {
+    Some(config) => config,
+    None => "",
+};
 
-async fn connect(control: &mut Control<'_>, stack: &Stack<'_>) -> Result<(), ()> {
-    let _guard = POWER_MUTEX.lock().await;
+/// Everything a sync does, retries included, has to fit in this
+const SYNC_BUDGET: Duration = Duration::from_secs(30);
+
+/// The scan and every join attempt share this part of the sync budget
+const JOIN_BUDGET: Duration = Duration::from_secs(20);
+const SCAN_TIMEOUT: Duration = Duration::from_secs(4);
+/// Longest a single network gets, so one that doesn't answer leaves time
+/// for the next
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(8);
+/// Not worth starting a join with less than this left
+const MIN_JOIN_TIME: Duration = Duration::from_secs(2);
+/// Longest a fast reconnect gets before falling back to the scan, out of
+/// the join budget
+const FAST_JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the first fetches after a fast reconnect get before the reused
+/// lease is given up on for DHCP, out of the sync budget
+const FAST_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The networks built into the firmware, for a badge without any in flash
+pub fn default_credentials() -> Credentials {
+    let mut credentials = Credentials::from_networks(&parse_networks(WIFI_SSID, WIFI_PASSWORD));
+    apply_static_ipv4(&mut credentials);
+
+    credentials
+}
+
+/// Gives the networks in `WIFI_IPV4` their static address, whether they came
+/// from the firmware or were set up later. Returns whether any changed, so
+/// the record only gets written when it needs to.
+pub fn apply_static_ipv4(credentials: &mut Credentials) -> bool {
+    let mut changed = false;
+
+    for (ssid, ipv4) in parse_static_ipv4(WIFI_IPV4) {
+        match ipv4.map(|ipv4| credentials.set_ipv4(ssid, ipv4)) {
+            Some(Some(updated)) => changed |= updated,
+            Some(None) => error!("WIFI_IPV4 names an unknown network"),
+            None => error!("WIFI_IPV4 has bad settings for a network"),
+        }
+    }
+
+    changed
+}
+
+/// DHCP, unless the network has a static address
+fn addressing(credentials: &Credentials, ssid: &str) -> ConfigV4 {
+    match credentials
+        .network(ssid)
+        .and_then(|network| network.ipv4.as_ref())
+    {
+        Some(ipv4) => ConfigV4::Static(to_static_config(ipv4)),
+        None => ConfigV4::Dhcp(Default::default()),
+    }
+}
+
+/// Listens for the known networks around
+async fn scan(control: &mut Control<'_>, networks: &[Network<'_>]) -> Scan {
+    let mut scan = Scan::default();
+    let mut scanner = control.scan(ScanOptions::default()).await;
+
+    while let Some(bss) = scanner.next().await {
+        let len = (bss.ssid_len as usize).min(bss.ssid.len());
+        let ap = AccessPoint {
+            bssid: bss.bssid,
+            channel: (bss.chanspec & 0xff) as u8,
+        };
+        scan.record(networks, &bss.ssid[..len], bss.rssi, ap);
+    }
+
+    scan
+}
+
+fn join_options(network: Network<'_>) -> JoinOptions<'_> {
+    if network.password.is_empty() {
+        JoinOptions::new_open()
+    } else {
+        JoinOptions::new(network.password)
+    }
+}
 
-    let mut connected_to_wifi = false;
+fn to_static_config(settings: &Ipv4Settings) -> StaticConfigV4 {
+    StaticConfigV4 {
+        address: Ipv4Cidr::new(Ipv4Address::from(settings.address), settings.prefix_len),
+        gateway: settings.gateway.map(Ipv4Address::from),
+        dns_servers: settings
+            .dns_servers
+            .iter()
+            .map(|dns| Ipv4Address::from(*dns))
+            .collect(),
+    }
+}
 
-    match with_timeout(
-        Duration::from_secs(20),
-        control.join(WIFI_SSID, JoinOptions::new(WIFI_PASSWORD)),
+fn from_static_config(config: &StaticConfigV4) -> Ipv4Settings {
+    Ipv4Settings {
+        address: config.address.address().octets(),
+        prefix_len: config.address.prefix_len(),
+        gateway: config.gateway.map(|gateway| gateway.octets()),
+        dns_servers: config
+            .dns_servers
+            .iter()
+            .take(MAX_DNS_SERVERS)
+            .map(|dns| dns.octets())
+            .collect(),
+    }
+}
+
+fn millis(duration: Duration) -> u32 {
+    duration.as_millis().try_into().unwrap_or(u32::MAX)
+}
+
+/// Waits for the network config until `deadline`, which only DHCP can
+/// miss. `false` if it's still down by then.
+async fn config_up(stack: &Stack<'_>, deadline: Instant) -> bool {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    with_timeout(remaining, stack.wait_config_up())
+        .await
+        .is_ok()
+}
+
+/// Rejoins the last network with its old lease, or its static address, as
+/// a static config, so there's no scan and no DHCP. The join and the config
+/// coming up share [`FAST_JOIN_TIMEOUT`], and stop at `deadline` too. cyw43
+/// can't be told the BSSID or channel to join on, so the chip still finds
+/// the access point itself and the remembered one is only logged.
+///
+/// Returns when the network accepted us, or `None` if it didn't or the
+/// config didn't come up in time.
+async fn fast_join(
+    control: &mut Control<'_>,
+    stack: &Stack<'_>,
+    network: Network<'_>,
+    reconnect: &Reconnect,
+    deadline: Instant,
+) -> Option<Instant> {
+    let deadline = deadline.min(Instant::now() + FAST_JOIN_TIMEOUT);
+    stack.set_config_v4(ConfigV4::Static(to_static_config(&reconnect.lease)));
+
+    let joined = with_timeout(
+        deadline.saturating_duration_since(Instant::now()),
+        control.join(network.ssid, join_options(network)),
     )
-    .await
+    .await;
+
+    let joined_at = Instant::now();
+
+    if !matches!(joined, Ok(Ok(_))) || !config_up(stack, deadline).await {
+        control.leave().await;
+        return None;
+    }
+
+    info!("rejoined {} with its last lease", network.ssid);
+    defmt::info!("Last joined through {}", reconnect.access_point);
+    Some(joined_at)
+}
+
+/// What the next wake needs to skip the scan and DHCP, if the clock can
+/// date the lease
+fn remember(
+    stack: &Stack<'_>,
+    ssid: &str,
+    access_point: Option<AccessPoint>,
+    now: Option<i64>,
+) -> Option<Reconnect> {
+    Some(Reconnect {
+        ssid: String::try_from(ssid).ok()?,
+        access_point,
+        lease: from_static_config(&stack.config_v4()?),
+        leased_at: now?,
+    })
+}
+
+/// Swaps a reused lease that got nowhere for a new one from DHCP on the same
+/// network, as the old address may have gone to someone else. `false` for a
+/// network with a static address, or when DHCP doesn't answer by `deadline`.
+async fn renew_lease(stack: &Stack<'_>, deadline: Instant) -> bool {
+    let _guard = POWER_MUTEX.lock().await;
+
+    let Some(reconnect) = RECONNECT.lock().await.take() else {
+        return false;
+    };
+
+    let credentials = CREDENTIALS.lock().await.clone();
+    let network = credentials.network(&reconnect.ssid);
+    if network.is_none_or(|network| network.ipv4.is_some()) {
+        return false;
+    }
+
+    let asking = Instant::now();
+    stack.set_config_v4(ConfigV4::Dhcp(Default::default()));
+    if !config_up(stack, deadline).await {
+        return false;
+    }
+
     {
-        Ok(Ok(_)) => {
-            connected_to_wifi = true;
-            info!("join successful");
-        }
-        Ok(Err(err)) => {
-            info!("join failed with status={}", err.status);
-        }
-        Err(_) => {
-            info!("join timed out");
-        }
+        let mut stats = SYNC_STATS.lock().await;
+        stats.fast_reconnect = false;
+        stats.network_ms = millis(asking.elapsed());
     }
 
-    if !connected_to_wifi {
-        return Err(());
+    let now = RTC_TIME.lock().await.map(to_unix_time);
+    *RECONNECT.lock().await = remember(stack, &reconnect.ssid, reconnect.access_point, now);
+    true
+}
+
+async fn connect(control: &mut Control<'_>, stack: &Stack<'_>) -> Result<(), SyncError> {
+    let _guard = POWER_MUTEX.lock().await;
+
+    let started = Instant::now();
+    let deadline = started + JOIN_BUDGET;
+    let credentials = CREDENTIALS.lock().await.clone();
+    let networks = credentials.networks();
+    let last_good = LAST_NETWORK.lock().await.clone();
+    let now = RTC_TIME.lock().await.map(to_unix_time);
+
+    let reconnect = RECONNECT.lock().await.clone();
+    if let Some(reconnect) = reconnect
+        && let Some(index) = reconnect.usable(&networks, now)
+    {
+        let joined = fast_join(control, stack, networks[index], &reconnect, deadline).await;
+        if let Some(joined) = joined {
+            let mut stats = SYNC_STATS.lock().await;
+            stats.fast_reconnect = true;
+            stats.join_ms = millis(joined - started);
+            stats.network_ms = millis(joined.elapsed());
+            return Ok(());
+        }
+
+        info!(
+            "fast reconnect to {} failed, scanning",
+            networks[index].ssid
+        );
+        *RECONNECT.lock().await = None;
     }
 
-    stack.wait_config_up().await;
+    // A scan that times out just leaves us trying the networks in order
+    let scan = with_timeout(SCAN_TIMEOUT, scan(control, &networks))
+        .await
+        .unwrap_or_default();
+
+    for index in scan.join_order(&networks, last_good.as_deref()) {
+        let network = networks[index];
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining < MIN_JOIN_TIME {
+            break;
+        }
+
+        stack.set_config_v4(addressing(&credentials, network.ssid));
+
+        match with_timeout(
+            remaining.min(NETWORK_TIMEOUT),
+            control.join(network.ssid, join_options(network)),
+        )
+        .await
+        {
+            Ok(Ok(_)) => {
+                let joined = Instant::now();
+                info!("joined {}", network.ssid);
+
+                if !config_up(stack, deadline).await {
+                    info!("no address from {} in time", network.ssid);
+                    control.leave().await;
+                    continue;
+                }
+
+                *LAST_NETWORK.lock().await = String::try_from(network.ssid).ok();
+
+                {
+                    let mut stats = SYNC_STATS.lock().await;
+                    stats.join_ms = millis(joined - started);
+                    stats.network_ms = millis(joined.elapsed());
+                }
+
+                *RECONNECT.lock().await =
+                    remember(stack, network.ssid, scan.access_point(index), now);
+                return Ok(());
+            }
+            Ok(Err(err)) => {
+                info!("join {} failed with status={}", network.ssid, err.status);
+            }
+            Err(_) => {
+                info!("join {} timed out", network.ssid);
+            }
+        }
+    }
 
-    Ok(())
+    Err(SyncError::Join)
 }
 
 async fn sync(
@@ -54,19 +341,44 @@ async fn sync(
     control: &mut Control<'static>,
     stack: Stack<'static>,
     rtc_device: &'static RtcDevice,
-    flash_driver: &'static FlashDevice,
-) {
-    if connect(control, &stack).await.is_ok() {
-        let (time_buf, weather_buf) = rx_buffer.split_at_mut(4096);
+    deadline: Instant,
+) -> Result<(), SyncError> {
+    connect(control, &stack).await?;
 
-        join(
-            fetch_time(&stack, time_buf, rtc_device),
-            fetch_weather(&stack, weather_buf, flash_driver),
-        )
-        .await;
+    // A reused lease gets part of the budget, leaving time for DHCP
+    let fast_reconnect = SYNC_STATS.lock().await.fast_reconnect;
+    let first_deadline = if fast_reconnect {
+        deadline.min(Instant::now() + FAST_FETCH_TIMEOUT)
+    } else {
+        deadline
+    };
 
-        control.leave().await;
+    let (mut time, mut weather) = join(
+        fetch_time(&stack, rtc_device),
+        fetch_weather(&stack, rx_buffer, first_deadline),
+    )
+    .await;
+
+    let unreachable = weather.is_err_and(|e| SyncError::Weather(e).is_network());
+    if fast_reconnect && unreachable && renew_lease(&stack, deadline).await {
+        info!("nothing got through on the last lease, retrying with DHCP");
+
+        let retried_time = async move {
+            match time {
+                Ok(()) => Ok(()),
+                Err(_) => fetch_time(&stack, rtc_device).await,
+            }
+        };
+        (time, weather) = join(retried_time, fetch_weather(&stack, rx_buffer, deadline)).await;
     }
+
+    let air_quality = fetch_air_quality(&stack, rx_buffer, deadline).await;
+
+    control.leave().await;
+
+    time.map_err(SyncError::Time)?;
+    weather.map_err(SyncError::Weather)?;
+    air_quality.map_err(SyncError::AirQuality)
 }
 
 async fn blink_sync(
@@ -77,14 +389,70 @@ async fn blink_sync(
     rtc_device: &'static RtcDevice,
     flash_driver: &'static FlashDevice,
 ) {
-    select(
+    let started = Instant::now();
+    let deadline = started + SYNC_BUDGET;
+    *SYNC_STATS.lock().await = SyncStats::default();
+    let had_reconnect = RECONNECT.lock().await.is_some();
+
+    let result = select(
         led::loop_breathe(user_led),
         with_timeout(
-            Duration::from_secs(30),
-            sync(rx_buffer, control, stack, rtc_device, flash_driver),
+            SYNC_BUDGET,
+            sync(rx_buffer, control, stack, rtc_device, deadline),
         ),
     )
     .await;
+
+    let outcome = match result {
+        Either::Second(Ok(outcome)) => outcome,
+        _ => Err(SyncError::Timeout),
+    };
+
+    let stats = {
+        let mut stats = SYNC_STATS.lock().await;
+        stats.sync_ms = millis(started.elapsed());
+        *stats
+    };
+
+    match outcome {
+        Ok(()) => {
+            info!(
+                "Sync done, {} weather and {} air quality attempts",
+                stats.weather_attempts, stats.air_quality_attempts
+            );
+            info!(
+                "Joined in {} ms by {}, network up {} ms later, sync took {} ms",
+                stats.join_ms,
+                if stats.fast_reconnect {
+                    "fast reconnect"
+                } else {
+                    "scan and DHCP"
+                },
+                stats.network_ms,
+                stats.sync_ms
+            );
+        }
+        Err(e) => error!("Sync failed: {:?}, {}", e, stats),
+    }
+
+    // A reused lease that got us nowhere may have been handed to someone
+    // else, so the next sync asks DHCP again
+    if stats.fast_reconnect && outcome.is_err_and(|e| !e.is_partial()) {
+        *RECONNECT.lock().await = None;
+    }
+
+    // The wake planner retries from the last sync that got the weather
+    if outcome.is_ok() || outcome.is_err_and(|e| e.is_partial()) {
+        *LAST_SYNC.lock().await = *RTC_TIME.lock().await;
+    }
+
+    *SYNC_ERROR.lock().await = outcome.err();
+
+    // Nothing was fetched without WiFi, so spare the flash the write unless
+    // the lease was dropped
+    if outcome != Err(SyncError::Join) || had_reconnect != RECONNECT.lock().await.is_some() {
+        flash::save_state(flash_driver).await;
+    }
 }
 
 #[embassy_executor::task]